@@ -1,41 +1,63 @@
 use std::cmp::Ordering;
 use macroquad::prelude::*;
-use crate::body::BodyLike;
+use crate::body::{BodyId, BodyLike};
 use crate::controller::Controller;
 use crate::num::{abs, num, Num, max, min};
 use std::collections::VecDeque;
 use std::time::Instant;
 use crate::config::EResult;
+use crate::frame::Frame;
+use crate::vector::{Vector, Vector2D};
 use self::basic::{
-    AppContext, Command, Components, default_font, load_font, Message,
-    Textures, UIStatus,
+    AppContext, Assets, Command, Components, default_font, Message, UIStatus,
 };
+use self::canvas::Screen;
 use self::config::Config;
+pub use self::config::Body;
 use self::mouse::{make_mouse_listener, MouseEvent, MouseListener, MouseTracker, SpreadStatus};
+use self::replay::Replay;
 use self::view::View;
 use self::functions::{Function, FunctionBox, Functions};
+use self::functions::collision::CollisionMode;
 
 mod basic;
 mod view;
 mod convert;
 mod config;
 mod ui;
+mod canvas;
+mod raster;
 mod mouse;
 mod functions;
+mod snapshot;
+mod replay;
+pub mod render;
 
 static ZOOM_IN_SCALE: Num = 1.1;
 static ZOOM_OUT_SCALE: Num = 1. / ZOOM_IN_SCALE;
+/// Contacts are located to within this share of a step.
+const CONTACT_PRECISION: Num = 1e-9;
+/// Clicks this close to a small body, in pixels, still pick it.
+const PICK_RADIUS: Num = 8.;
 
 async fn event(mut context: AppContext, functions: &mut Functions) -> AppContext {
+    let mut mouse_taken = false;
     for func in functions {
         if let Some(command) = func.event(&context) {
+            mouse_taken |= command.takes_mouse();
             context = handle(context, command).await;
         }
     }
     
     let command = if let Some(code) = get_last_key_pressed() {
         check_keyboard(&context, code).await
-    } else if let Some(command) = context.mouse_tracker.update(()).await {
+    } else if mouse_taken {
+        Command::None
+    } else if let Some(command) = {
+        let (x, y) = mouse_position();
+        let hovered = body_at(&context, Vector2D::new(x, y));
+        context.mouse_tracker.update(hovered).await
+    } {
         command
     } else {
         Command::None
@@ -55,12 +77,21 @@ const SCALE_MAX: Num = 0.9;
 const SCALE_MAX_RESIZE: Num = 0.5;
 
 async fn auto_zoom(mut context: AppContext) -> AppContext {
+    if fit_view(&mut context) {
+        context = Box::pin(refresh(context)).await;
+    }
+    
+    context
+}
+
+/// Zoom so that the bodies fill the view, returning whether it had to.
+fn fit_view(context: &mut AppContext) -> bool {
     let mut max_d = num(0);
     let width = context.view.half_width();
     let height = context.view.half_height();
     for body in context.controller.iter()
     {
-        let pos = context.view.convert(*(body.pos())).0;
+        let pos = context.view.project(*body.pos());
         let x = abs(pos.x()) / width;
         let y = abs(pos.y()) / height;
         let max = if let Ordering::Greater = x.total_cmp(&y) {
@@ -78,18 +109,18 @@ async fn auto_zoom(mut context: AppContext) -> AppContext {
     if max_d > SCALE_MAX {
         context.view.zoom(min(SCALE_MAX_RESIZE, max_d));
         // context.add_message(format!("Resize view: {}", SCALE_MAX_RESIZE).into());
-        context = Box::pin(refresh(context)).await;
+        true
     } else if max_d < SCALE_MIN {
         context.view.zoom(max(SCALE_MIN_RESIZE, max_d));
         // context.add_message(format!("Resize view: {}", SCALE_MIN_RESIZE).into());
-        context = Box::pin(refresh(context)).await;
+        true
+    } else {
+        false
     }
-    
-    context
 }
 
 async fn add_trail(mut context: AppContext) -> AppContext {
-    let mut new_trails = context.bodies.clone();
+    let mut new_trails: Vec<_> = context.bodies.iter().filter(|(x, _)| !x.test_particle).copied().collect();
     new_trails.iter_mut()
         .for_each(|(point, id)| {
             let body = context.controller.get_body(*id).unwrap();
@@ -148,8 +179,17 @@ async fn update_functions(mut context: AppContext, functions: &mut Functions) ->
 }
 
 async fn update(mut context: AppContext) -> AppContext {
+    // Without a window, the view keeps the size it was made with.
+    if context.assets.is_some() {
+        context.view.refresh(num(screen_width()), num(screen_height()));
+    }
+    update_scene(context).await
+}
+
+/// Bring what is drawn up to date with the bodies, keeping the size of the view.
+async fn update_scene(mut context: AppContext) -> AppContext {
+    context.view.follow(&context.controller);
     context.bodies = context.view.parse(&context);
-    context.view.refresh(num(screen_width()), num(screen_height()));
     
     update_trail(&mut context).await;
     
@@ -164,25 +204,52 @@ async fn step(mut context: AppContext) -> AppContext {
     let (delta, steps) = match context.steps.cmp(&0) {
         Ordering::Equal => { return context; }
         Ordering::Greater => {
-            (context.config.delta, context.steps)
+            (context.config.physics.delta, context.steps)
         }
         Ordering::Less => {
-            (-context.config.delta, -context.steps)
+            (-context.config.physics.delta, -context.steps)
         }
     };
     
     let count = steps / step_per_trail;
+    let adaptive = context.config.physics.integrator.is_adaptive();
     for _ in 0..count {
-        for _ in 0..step_per_trail {
-            context.controller.update(delta);
-            context.time += delta;
-        }
+        let stopped = if adaptive {
+            // Advance by simulated time, the integrator picks its own sub steps.
+            !advance(&mut context, delta * step_per_trail as Num)
+        } else {
+            (0..step_per_trail).any(|_| !advance(&mut context, delta))
+        };
         context = add_trail(context).await;
+        if stopped {
+            break;
+        }
     }
     
     context
 }
 
+/// Advance the simulation by `delta`, returning false when it stopped early at the contact of two bodies.
+fn advance(context: &mut AppContext, delta: Num) -> bool {
+    let mut completed = true;
+    if context.stop_on_contact {
+        let contact = functions::collision::contact_distance(&context.config);
+        let precision = abs(delta) * CONTACT_PRECISION;
+        context.contact = context.controller.update_until_contact(delta, contact, precision);
+        completed = context.contact.is_none();
+    } else {
+        context.controller.update(delta);
+    }
+    context.time = context.controller.total_seconds();
+    completed
+}
+
+/// Take the bodies as edited by hand as the new start, drift included.
+async fn edited(mut context: AppContext) -> AppContext {
+    context.controller.rebase();
+    refresh(context).await
+}
+
 fn prepare_delta(x: Num) -> Num {
     x / -100.
 }
@@ -197,7 +264,7 @@ async fn screenshot(mut context: AppContext) -> AppContext {
     let running = context.running;
     context.running = false;
     
-    ui::draw(&context, &Vec::new()).await;
+    ui::draw(&context, &Vec::new(), &mut Screen::new(&context));
     let img = get_screen_data();
     next_frame().await;
     
@@ -215,10 +282,8 @@ async fn screenshot(mut context: AppContext) -> AppContext {
     next_frame().await;
     
     let path = format!("./{}.png", chrono::Local::now().format("%Y%m%d_%H%M%S"));
-    match std::panic::catch_unwind(
-        || img.export_png(path.as_str())
-    ) {
-        Err(err) => { context.add_message(format!("Failed to save screenshot: {:?}", err).into()); }
+    match render::save_png(&img, path.as_ref()) {
+        Err(err) => { context.add_message(format!("Failed to save screenshot: {:#}", err).into()); }
         Ok(_) => { context.add_message(format!("Screenshot saved: {:?}", path).into()); }
     }
     context.running = running;
@@ -241,6 +306,10 @@ async fn check_keyboard(context: &AppContext, code: KeyCode) -> Command {
         KeyCode::B => Command::ToggleUI(Components::Bodies),
         KeyCode::I => Command::ToggleUI(Components::Trail),
         KeyCode::S => Command::Screenshot,
+        KeyCode::F5 => Command::SaveSnapshot,
+        KeyCode::F9 => Command::LoadSnapshot,
+        KeyCode::F => Command::NextFrame,
+        KeyCode::Tab => Command::FollowNext,
         KeyCode::P => Command::AddMessage(format!("Mouse at: {:?}", mouse_position()).into()),
         KeyCode::R => Command::ResetView,
         KeyCode::X => Command::AutoZoom,
@@ -250,6 +319,9 @@ async fn check_keyboard(context: &AppContext, code: KeyCode) -> Command {
 
 async fn handle(mut context: AppContext, command: Command) -> AppContext {
     // println!("Handle event: {:?}", command);
+    if let Some(recording) = &mut context.recording {
+        recording.record(context.frame, &command);
+    }
     match command {
         Command::RotateView(dx, dy) => {
             let dx = prepare_delta(dx);
@@ -292,6 +364,107 @@ async fn handle(mut context: AppContext, command: Command) -> AppContext {
         Command::Screenshot => {
             context = screenshot(context).await;
         }
+        Command::SaveSnapshot => {
+            match snapshot::save(&context) {
+                Err(err) => { context.add_message(format!("Failed to save snapshot: {:#}", err).into()); }
+                Ok(path) => { context.add_message(format!("Snapshot saved: {:?}", path).into()); }
+            }
+        }
+        Command::LoadSnapshot => {
+            match snapshot::latest().map(|path| (snapshot::load(&path), path)) {
+                None => { context.add_message("No snapshot found".into()); }
+                Some((Err(err), _)) => { context.add_message(format!("Failed to load snapshot: {:#}", err).into()); }
+                Some((Ok(config), path)) => {
+                    context = Box::pin(handle(context, Command::LoadConfig(Box::new(config)))).await;
+                    context.add_message(format!("Snapshot loaded: {:?}", path).into());
+                }
+            }
+        }
+        Command::LoadConfig(config) => {
+            snapshot::restore(&mut context, *config);
+            context = refresh(context).await;
+        }
+        Command::NextFrame => {
+            let frame = cycle_frame(&context);
+            context.view.set_frame(frame);
+            context = refresh(context).await;
+            context.add_message(format!("Frame: {}", frame).into());
+        }
+        Command::Select(selected) => {
+            // Selecting the selected body again closes its details.
+            context.selected = if selected == context.selected { None } else { selected };
+        }
+        Command::Follow(target) => {
+            context.view.set_target(target);
+            context = refresh(context).await;
+            context.add_message(match target {
+                Some(id) => format!("Following body {}", id).into(),
+                None => "Stopped following".into(),
+            });
+        }
+        Command::FollowNext => {
+            let mut ids: Vec<BodyId> = context.controller.iter()
+                .filter(|x| !x.is_test_particle())
+                .map(|x| *x.id())
+                .collect();
+            ids.sort();
+            let current = context.view.target();
+            let next = ids.into_iter().find(|&x| current.is_none_or(|id| x > id));
+            context = Box::pin(handle(context, Command::Follow(next))).await;
+        }
+        Command::SetRunning(running) => {
+            context.running = running;
+        }
+        Command::SetCollisionDetect(on) => {
+            context.stop_on_contact = on;
+            if !on {
+                context.contact = None;
+            }
+            context.add_message(format!("Collision Detect turned {}", if on { "on" } else { "off" }).into());
+        }
+        Command::SetEditing(on) => {
+            context.editing = on;
+            context.running = !on;
+            context.add_message(format!("Edit mode turned {}", if on { "on" } else { "off" }).into());
+        }
+        Command::MoveBody(id, pos) => {
+            if context.controller.set_pos(id, pos) {
+                context = edited(context).await;
+            }
+        }
+        Command::SetSpeed(id, speed) => {
+            if context.controller.set_speed(id, speed) {
+                context = edited(context).await;
+            }
+        }
+        Command::Spawn(pos, mass) => {
+            let id = context.controller.next_id();
+            context.controller.add(Body::new(id, pos, Vector::origin(), mass));
+            context = edited(context).await;
+            context.selected = Some(id);
+            context.add_message(format!("Body {} added", id).into());
+        }
+        Command::RemoveBody(id) => {
+            if context.controller.remove(id).is_some() {
+                if context.selected == Some(id) {
+                    context.selected = None;
+                }
+                context = edited(context).await;
+                context.add_message(format!("Body {} removed", id).into());
+            }
+        }
+        Command::SaveScenario => {
+            match snapshot::save_scenario(&context) {
+                Err(err) => { context.add_message(format!("Failed to save scenario: {:#}", err).into()); }
+                Ok(path) => { context.add_message(format!("Scenario saved: {:?}", path).into()); }
+            }
+        }
+        Command::Restore(keyframe) => {
+            context.controller.restore(&keyframe);
+            context.time = keyframe.time;
+            context.contact = None;
+            context = refresh(context).await;
+        }
         Command::AddSpeed(d) => {
             context.steps += d;
             context.add_message(format!("Steps set to: {}", context.steps).into());
@@ -305,7 +478,59 @@ async fn handle(mut context: AppContext, command: Command) -> AppContext {
     context
 }
 
-fn check_actions(event: MouseEvent, _: ()) -> SpreadStatus<Command> {
+/// The frame after the current one: inertial, center of mass, each massive body,
+/// then co-rotating with the configured pair or else the two heaviest bodies.
+fn cycle_frame(context: &AppContext) -> Frame {
+    let mut massive: Vec<&Body> = context.controller.iter().filter(|x| !x.is_test_particle()).collect();
+    let pair = match context.config.frame {
+        Frame::CoRotating(a, b) => Some((a, b)),
+        _ => {
+            massive.sort_by(|x, y| y.mass().total_cmp(x.mass()));
+            (massive.len() >= 2).then(|| (*massive[0].id(), *massive[1].id()))
+        }
+    };
+    massive.sort_by_key(|x| *x.id());
+    let next_body = |after: Option<BodyId>| massive.iter()
+        .map(|x| *x.id())
+        .find(|&x| after.is_none_or(|id| x > id));
+    let next = match context.view.frame() {
+        Frame::Inertial => Some(Frame::CenterOfMass),
+        Frame::CenterOfMass => next_body(None).map(Frame::Body),
+        Frame::Body(id) => next_body(Some(id)).map(Frame::Body),
+        Frame::CoRotating(..) => Some(Frame::Inertial),
+    };
+    next.or(pair.map(|(a, b)| Frame::CoRotating(a, b))).unwrap_or(Frame::Inertial)
+}
+
+/// The massive body drawn at `pos` on the screen, the one on top where they overlap.
+fn body_at(context: &AppContext, pos: Vector2D) -> Option<BodyId> {
+    // Bodies are drawn in order, so later ones cover earlier ones.
+    context.bodies.iter()
+        .rev()
+        .filter(|(point, _)| !point.test_particle)
+        .find(|(point, _)| {
+            let center = Vector2D::new(context.view.get_x(point), context.view.get_y(point));
+            center.distance(pos) <= max(point.radius, num(PICK_RADIUS))
+        })
+        .map(|(_, id)| *id)
+}
+
+/// Clicks on a body select it, or deselect it if it is already, double clicks follow it.
+fn check_pick(event: MouseEvent, hovered: Option<BodyId>) -> SpreadStatus<Command> {
+    match (event, hovered) {
+        (MouseEvent::Click(_), Some(id)) => {
+            SpreadStatus::Stop(Command::Select(Some(id)))
+        }
+        (MouseEvent::DoubleClick(_), Some(id)) => {
+            SpreadStatus::Stop(Command::Follow(Some(id)))
+        }
+        _ => {
+            SpreadStatus::Route(None)
+        }
+    }
+}
+
+fn check_actions(event: MouseEvent, _: Option<BodyId>) -> SpreadStatus<Command> {
     match event {
         MouseEvent::Click(_) => {
             SpreadStatus::Stop(Command::TogglePause)
@@ -322,7 +547,7 @@ fn check_actions(event: MouseEvent, _: ()) -> SpreadStatus<Command> {
     }
 }
 
-fn check_view_rotation(event: MouseEvent, _: ()) -> SpreadStatus<Command> {
+fn check_view_rotation(event: MouseEvent, _: Option<BodyId>) -> SpreadStatus<Command> {
     match event {
         MouseEvent::Dragging { diff, .. } => {
             let cmd = Command::RotateView(num(diff.0), num(diff.1));
@@ -365,61 +590,107 @@ async fn init_impl(config: Config) -> (AppContext, Functions) {
     );
     next_frame().await;
     
+    let assets = Assets::load(&config).await;
+    let (width, height) = (num(screen_width()), num(screen_height()));
+    new_context(config, Some(assets), width, height)
+}
+
+/// The app as it starts, for a view of `width` by `height`, and without a window if there are no `assets`.
+fn new_context(config: Config, assets: Option<Assets>, width: Num, height: Num) -> (AppContext, Functions) {
     let bodies = config.bodies.clone();
-    let tooltip_font = load_font(config.tooltip_font.as_ref()).await;
-    let instruction_font = if config.tooltip_font == config.instruction_font {
-        tooltip_font.clone()
-    } else {
-        load_font(config.instruction_font.as_ref()).await
-    };
-    
-    
-    let listeners: Vec<Box<dyn MouseListener<Msg = (), Result = Command>>> = vec![
+    let listeners: Vec<Box<dyn MouseListener<Msg = Option<BodyId>, Result = Command>>> = vec![
         Box::new(make_mouse_listener(check_view_rotation)),
+        Box::new(make_mouse_listener(check_pick)),
         Box::new(make_mouse_listener(check_actions)),
     ];
-    let mouse_tracker: MouseTracker<(), Command>
+    let mouse_tracker: MouseTracker<Option<BodyId>, Command>
         = MouseTracker::from_vec(MouseButton::Left, listeners);
     
     let mut ui_status = UIStatus::default();
     ui_status.toggle(Components::Help);
     ui_status.toggle(Components::Axis);
+    ui_status.toggle(Components::CollisionLog);
     
     let steps = config.steps;
+    let time = config.time;
+    let stop_on_contact = config.collision != CollisionMode::Ignore;
+    let integrator = config.physics.integrator.build(config.physics.step_control());
+    let gravity = config.physics.gravity();
+    let mut controller = Controller::new(bodies, integrator, gravity);
+    controller.set_time(time);
+    let mut view = View::new(width, height);
+    if let Some(data) = &config.view {
+        view.set_data(data.clone());
+    }
+    view.set_frame(config.frame);
+    view.set_target(config.follow);
     
     let functions = vec![
-        Box::new(functions::collision::CollisionDetect::new(&config)) as FunctionBox
+        Box::new(functions::collision::CollisionDetect::new(&config)) as FunctionBox,
+        Box::new(functions::diagnostics::Diagnostics::new(&config)) as FunctionBox,
+        Box::new(functions::events::Events::new(&config)) as FunctionBox,
+        Box::new(functions::details::Details::new(&config)) as FunctionBox,
+        Box::new(functions::editor::Editor::new(&config)) as FunctionBox,
+        Box::new(functions::timeline::Timeline::new(&config)) as FunctionBox,
     ];
     
-    let textures = Textures::load();
-    
     let context = AppContext {
         config,
         points: VecDeque::new(),
         messages: VecDeque::from([Message::new("Press [H] to show help".to_string())]),
-        controller: Controller::new(bodies),
+        controller,
         running: true,
         exit: false,
         bodies: Vec::new(),
-        tooltip_font,
-        instruction_font,
         ui_status,
         mouse_tracker,
-        view: View::new(num(screen_width()), num(screen_height())),
-        textures,
+        view,
+        assets,
         steps,
-        time: 0.0,
+        time,
+        stop_on_contact,
+        contact: None,
+        editing: false,
+        selected: None,
+        frame: 0,
+        recording: None,
     };
     
     (context, functions)
 }
 
-pub async fn app(config: Config) {
+/// Handle the commands of the replay for this frame, instead of those of the user, who may only leave.
+async fn play(mut context: AppContext, replay: &mut Replay) -> AppContext {
+    if is_key_pressed(KeyCode::Escape) {
+        context.exit = true;
+        return context;
+    }
+    
+    for command in replay.take(context.frame) {
+        context = handle(context, command).await;
+    }
+    if context.frame == replay.frames {
+        context.running = false;
+        context.add_message("Replay finished, press [ESC] to exit".into());
+    }
+    context
+}
+
+pub async fn app(config: Config, mut replay: Option<Replay>) {
     let (mut context, mut functions) = init(config).await;
     
-    context = auto_zoom(context).await;
+    if context.config.view.is_none() {
+        context = auto_zoom(context).await;
+    }
+    let record_file = crate::config::record_file();
+    if record_file.is_some() {
+        context.recording = Some(Replay::start(&context));
+    }
     loop {
-        context = event(context, &mut functions).await;
+        context = match &mut replay {
+            Some(replay) => play(context, replay).await,
+            None => event(context, &mut functions).await,
+        };
         if context.exit {
             break;
         }
@@ -428,14 +699,27 @@ pub async fn app(config: Config) {
             context = update(context).await;
             context = update_functions(context, &mut functions).await;
         }
-        ui::draw(&context, &functions).await;
+        ui::draw(&context, &functions, &mut Screen::new(&context));
         draw_functions(&context, &functions).await;
         next_frame().await;
+        context.frame += 1;
+    }
+    
+    if let (Some(path), Some(recording)) = (record_file, &mut context.recording) {
+        recording.frames = context.frame;
+        match recording.save(path) {
+            Err(err) => eprintln!("{:#}", err),
+            Ok(_) => eprintln!("Replay saved: {:?}", path),
+        }
     }
 }
 
 pub fn main() -> EResult<()> {
-    let config: Config = crate::config::init()?;
+    let replay = crate::config::replay_file().map(Replay::load).transpose()?;
+    let config: Config = match &replay {
+        Some(replay) => replay.config.clone(),
+        None => crate::config::init()?,
+    };
 
     macroquad::Window::from_config(
         Conf {
@@ -445,7 +729,7 @@ pub fn main() -> EResult<()> {
             window_height: config.window_height,
             ..Default::default()
         },
-        app(config),
+        app(config, replay),
     );
 
 
@@ -4,14 +4,16 @@ use macroquad::color::{Color, WHITE};
 use macroquad::prelude::load_ttf_font;
 use macroquad::text::{Font, load_ttf_font_from_bytes};
 use macroquad::texture::Texture2D;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use crate::apps::app::MouseTracker;
 use crate::apps::app::view::View;
 use crate::body::BodyId;
 use super::config::{Config, Body};
+use super::replay::Replay;
 use crate::controller::Controller;
+use crate::history::Keyframe;
 use crate::num::Num;
-use crate::vector::Vector2D;
+use crate::vector::{Vector, Vector2D};
 
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Point {
@@ -20,11 +22,26 @@ pub struct Point {
     pub color: Color,
     pub radius: Num,
     pub depth: Num,
+    /// Drawn as a plain dot, without trail nor tooltip.
+    pub test_particle: bool,
 }
 
 
+/// Options as lists of none or one, as TOML has no null, for replay files.
+mod maybe {
+    use super::*;
+    
+    pub fn serialize<T: Serialize, S: Serializer>(x: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        x.as_slice().serialize(serializer)
+    }
+    
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+        Ok(Vec::<T>::deserialize(deserializer)?.into_iter().next())
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Command {
     RotateView(Num, Num),
     ZoomIn,
@@ -34,13 +51,55 @@ pub enum Command {
     Exit,
     TogglePause,
     Screenshot,
+    SaveSnapshot,
+    /// Load the latest snapshot, which is recorded as the `LoadConfig` of what it held.
+    LoadSnapshot,
+    LoadConfig(#[serde(deserialize_with = "crate::config::deserialize_config")] Box<Config>),
+    NextFrame,
+    Select(#[serde(with = "maybe")] Option<BodyId>),
+    Follow(#[serde(with = "maybe")] Option<BodyId>),
+    FollowNext,
+    SetRunning(bool),
+    /// Stop steps at the first contact of two bodies, for the collision function to deal with.
+    SetCollisionDetect(bool),
+    SetEditing(bool),
+    MoveBody(BodyId, Vector),
+    SetSpeed(BodyId, Vector),
+    /// Add a body of the given mass, at rest.
+    Spawn(Vector, Num),
+    RemoveBody(BodyId),
+    SaveScenario,
+    /// Go back to a recorded state.
+    Restore(Keyframe<Body>),
     AddSpeed(isize),
     ToggleUI(Components),
+    #[serde(skip)]
     AddMessage(Message),
     MultiCommand(Vec<Command>),
     None,
 }
 
+impl Command {
+    /// Whether the command answers a mouse press, which the mouse tracker should then not see.
+    pub fn takes_mouse(&self) -> bool {
+        match self {
+            Command::MoveBody(..) | Command::SetSpeed(..) | Command::Spawn(..) | Command::Restore(..) => true,
+            Command::MultiCommand(cmds) => cmds.iter().any(Command::takes_mouse),
+            _ => false,
+        }
+    }
+    
+    /// Whether replays repeat the command. Those only writing files or showing messages are left out,
+    /// so are bundles of commands, whose parts are handled one by one.
+    pub fn is_replayed(&self) -> bool {
+        !matches!(
+            self,
+            Command::Screenshot | Command::SaveSnapshot | Command::LoadSnapshot | Command::SaveScenario | Command::Exit
+                | Command::AddMessage(_) | Command::MultiCommand(_) | Command::None
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Message {
     pub content: String,
@@ -92,6 +151,26 @@ impl Textures {
     }
 }
 
+/// What drawing to the window takes, which can only be loaded once it is open.
+pub struct Assets {
+    pub tooltip_font: Font,
+    pub instruction_font: Font,
+    pub textures: Textures,
+}
+
+impl Assets {
+    pub async fn load(config: &Config) -> Self {
+        let tooltip_font = load_font(config.tooltip_font.as_ref()).await;
+        let instruction_font = if config.tooltip_font == config.instruction_font {
+            tooltip_font.clone()
+        } else {
+            load_font(config.instruction_font.as_ref()).await
+        };
+        
+        Self { tooltip_font, instruction_font, textures: Textures::load() }
+    }
+}
+
 pub struct AppContext {
     pub config: Config,
     pub points: VecDeque<Point>,
@@ -100,14 +179,26 @@ pub struct AppContext {
     pub controller: Controller<Body>,
     pub exit: bool,
     pub running: bool,
-    pub tooltip_font: Font,
-    pub instruction_font: Font,
     pub ui_status: UIStatus,
-    pub mouse_tracker: MouseTracker<(), Command>,
+    /// Listeners get the body under the mouse, if any.
+    pub mouse_tracker: MouseTracker<Option<BodyId>, Command>,
     pub view: View,
     pub steps: isize,
     pub time: Num,
-    pub textures: Textures,
+    /// None without a window, when drawing to a raster.
+    pub assets: Option<Assets>,
+    /// Cut steps short at the first contact of two bodies, while collisions are detected.
+    pub stop_on_contact: bool,
+    /// The bodies the last step stopped at the contact of.
+    pub contact: Option<(BodyId, BodyId)>,
+    /// Bodies are moved and added with the mouse, while the simulation is kept paused.
+    pub editing: bool,
+    /// Body shown in the detail panel.
+    pub selected: Option<BodyId>,
+    /// Frames drawn so far.
+    pub frame: u64,
+    /// The session so far, when it is recorded.
+    pub recording: Option<Replay>,
 }
 
 impl AppContext {
@@ -115,6 +206,11 @@ impl AppContext {
         // println!("{}", message.content);
         self.messages.push_back(message);
     }
+    
+    /// The font of instructions, if loaded, for drawing to the window.
+    pub fn instruction_font(&self) -> Option<&Font> {
+        self.assets.as_ref().map(|x| &x.instruction_font)
+    }
 }
 
 #[derive(Debug, Copy, Clone, Deserialize, Serialize)]
@@ -156,6 +252,8 @@ pub enum Components {
     Bodies,
     Message,
     Trail,
+    Diagnostics,
+    CollisionLog,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -167,6 +265,8 @@ pub struct UIStatus {
     bodies: OnOffStatus,
     message: OnOffStatus,
     trail: OnOffStatus,
+    diagnostics: OnOffStatus,
+    collision_log: OnOffStatus,
 }
 
 impl UIStatus {
@@ -179,6 +279,8 @@ impl UIStatus {
             Components::Message => self.message.is_on(),
             Components::Bodies => self.bodies.is_on(),
             Components::Trail => self.trail.is_on(),
+            Components::Diagnostics => self.diagnostics.is_on(),
+            Components::CollisionLog => self.collision_log.is_on(),
         }
     }
     pub fn toggle(&mut self, which: Components) -> OnOffStatus {
@@ -190,6 +292,8 @@ impl UIStatus {
             Components::Message => self.message.toggle(),
             Components::Bodies => self.bodies.toggle(),
             Components::Trail => self.trail.toggle(),
+            Components::Diagnostics => self.diagnostics.toggle(),
+            Components::CollisionLog => self.collision_log.toggle(),
         }
     }
 }
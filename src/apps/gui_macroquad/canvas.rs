@@ -0,0 +1,95 @@
+use macroquad::prelude::*;
+use super::basic::{AppContext, Assets};
+
+/// Fonts of the app, picked by use, as each canvas loads them its own way.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontKind {
+    Tooltip,
+    Instruction,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TextStyle {
+    pub font: FontKind,
+    pub size: u16,
+    pub color: Color,
+}
+
+/// Something the scene is drawn to, in pixels from the top left corner.
+pub trait Canvas {
+    fn width(&self) -> f32;
+    fn height(&self) -> f32;
+    /// Cover the whole canvas with the background picture.
+    fn background(&mut self);
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color);
+    fn circle(&mut self, x: f32, y: f32, r: f32, color: Color);
+    fn rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color);
+    /// Draw `text` on a baseline at `y`.
+    fn text(&mut self, text: &str, x: f32, y: f32, style: TextStyle);
+}
+
+/// The window, through macroquad.
+pub struct Screen<'a> {
+    assets: Option<&'a Assets>,
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(context: &'a AppContext) -> Self {
+        Self { assets: context.assets.as_ref() }
+    }
+}
+
+impl Canvas for Screen<'_> {
+    fn width(&self) -> f32 {
+        screen_width()
+    }
+    
+    fn height(&self) -> f32 {
+        screen_height()
+    }
+    
+    fn background(&mut self) {
+        let Some(assets) = self.assets else {
+            clear_background(BLACK);
+            return;
+        };
+        draw_texture_ex(
+            &assets.textures.background,
+            0., 0., WHITE,
+            DrawTextureParams {
+                dest_size: Some(Vec2 { x: screen_width(), y: screen_height() }),
+                ..Default::default()
+            },
+        );
+    }
+    
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        draw_line(x1, y1, x2, y2, thickness, color);
+    }
+    
+    fn circle(&mut self, x: f32, y: f32, r: f32, color: Color) {
+        draw_circle(x, y, r, color);
+    }
+    
+    fn rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        draw_rectangle(x, y, w, h, color);
+    }
+    
+    fn text(&mut self, text: &str, x: f32, y: f32, style: TextStyle) {
+        let font = self.assets.map(|x| match style.font {
+            FontKind::Tooltip => &x.tooltip_font,
+            FontKind::Instruction => &x.instruction_font,
+        });
+        draw_text_ex(
+            text,
+            x,
+            y,
+            TextParams {
+                font,
+                font_size: style.size,
+                color: style.color,
+                ..Default::default()
+            },
+        );
+    }
+}
@@ -4,7 +4,7 @@ use crate::num::{Num, num, Int};
 use crate::vector::Vector;
 use std::sync::Mutex;
 
-#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+#[derive(PartialEq, Debug, Clone, Serialize)]
 pub enum AttrValue {
     Num(Num),
     Vector(Vector),
@@ -16,6 +16,44 @@ pub enum AttrValue {
     ColorWrap(ColorWrap),
 }
 
+#[derive(Deserialize)]
+#[serde(remote = "AttrValue")]
+enum TaggedAttrValue {
+    Num(Num),
+    Vector(Vector),
+    Int(Int),
+    USize(usize),
+    ISize(isize),
+    Bool(bool),
+    String(String),
+    ColorWrap(ColorWrap),
+}
+
+/// Either tagged like `{ USize = 1 }`, as attributes are saved, or a plain number, bool or string.
+impl<'de> Deserialize<'de> for AttrValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Helper {
+            Num(Num),
+            Bool(bool),
+            String(String),
+            #[serde(with = "TaggedAttrValue")]
+            Tagged(AttrValue),
+        }
+        
+        Ok(match Helper::deserialize(deserializer)? {
+            Helper::Num(x) => AttrValue::Num(x),
+            Helper::Bool(x) => AttrValue::Bool(x),
+            Helper::String(x) => AttrValue::String(x),
+            Helper::Tagged(x) => x,
+        })
+    }
+}
+
 macro_rules! make_default {
     ($name: ident, $value: expr, $typ: ident) => {
         fn $name() -> $typ {
@@ -52,6 +90,7 @@ impl Into<Color> for ColorWrap {
 mod body {
     use std::collections::HashMap;
     use serde::{Deserializer, Serializer};
+    use serde::de::Error;
     use crate::body::{BodyId, BodyLike};
     use super::*;
     
@@ -107,7 +146,7 @@ mod body {
     
     fn color() -> ColorWrap {
         let idx = unsafe { CURRENT_COLOR_INDEX };
-        let color = DEFAULT_COLORS[idx];
+        let color = DEFAULT_COLORS[idx % DEFAULT_COLORS.len()];
         unsafe { CURRENT_COLOR_INDEX += 1; }
         ColorWrap {
             r: color[0],
@@ -169,12 +208,19 @@ mod body {
         func(&BODY_INFO)
     }
     
+    /// The body an orbit is around, as placed by the config loader.
+    #[derive(Deserialize)]
+    struct OrbitHelper {
+        around: BodyId,
+    }
+    
     #[derive(Debug, Copy, Clone, PartialEq)]
     pub struct Body {
         id: BodyId,
         pos: Vector,
         speed: Vector,
         mass: Num,
+        test_particle: bool,
         color: ColorWrap,
         trail_color: ColorWrap,
     }
@@ -191,17 +237,30 @@ mod body {
                 pos: Option<Vector>,
                 speed: Option<Vector>,
                 mass: Option<Num>,
+                #[serde(default)]
+                test_particle: bool,
                 color: Option<ColorWrap>,
                 trail_color: Option<ColorWrap>,
+                orbit: Option<OrbitHelper>,
                 #[serde(flatten)]
                 attrs: HashMap<String, AttrValue>,
             }
             
-            let body_helper = BodyHelper::deserialize(deserializer)?;
+            let mut body_helper = BodyHelper::deserialize(deserializer)?;
             let id = body_helper.id.unwrap_or_else(id);
-            let pos = body_helper.pos.unwrap_or_else(Vector::origin);
-            let speed = body_helper.speed.unwrap_or_else(Vector::origin);
-            let mass = body_helper.mass.unwrap_or_else(mass);
+            let pos = body_helper.pos.unwrap_or_default();
+            let speed = body_helper.speed.unwrap_or_default();
+            let mass = match body_helper.mass {
+                Some(x) if body_helper.test_particle && x != num(0) => {
+                    return Err(D::Error::custom(format!("Body {} is a test particle, which has no mass", id)));
+                }
+                Some(x) => x,
+                None if body_helper.test_particle => num(0),
+                None => mass(),
+            };
+            if let Some(orbit) = &body_helper.orbit {
+                body_helper.attrs.insert("around".into(), AttrValue::USize(orbit.around));
+            }
             let color = body_helper.color.unwrap_or_else(color);
             let trail_color = body_helper.trail_color.unwrap_or_else(trail_color);
             
@@ -213,7 +272,8 @@ mod body {
                     .extend(body_helper.attrs)
             );
             
-            Ok(Body { id, pos, speed, mass, color, trail_color })
+            let test_particle = body_helper.test_particle;
+            Ok(Body { id, pos, speed, mass, test_particle, color, trail_color })
         }
     }
     
@@ -225,6 +285,8 @@ mod body {
                 pos: Vector,
                 speed: Vector,
                 mass: Num,
+                #[serde(skip_serializing_if = "std::ops::Not::not")]
+                test_particle: bool,
                 color: ColorWrap,
                 trail_color: ColorWrap,
                 #[serde(flatten)]
@@ -238,6 +300,7 @@ mod body {
                 pos: self.pos,
                 speed: self.speed,
                 mass: self.mass,
+                test_particle: self.test_particle,
                 attrs: &get_body_info(
                     |info| info.lock().unwrap().get(&self.id).unwrap().clone()
                 )
@@ -258,6 +321,7 @@ mod body {
         fn pos_mut(&mut self) -> &mut Vector { &mut self.pos }
         fn speed_mut(&mut self) -> &mut Vector { &mut self.speed }
         fn mass_mut(&mut self) -> &mut Num { &mut self.mass }
+        fn is_test_particle(&self) -> bool { self.test_particle }
         fn get_attr(&self, name: &String) -> Option<AttrValue> {
             get_body_info(
                 |info| {
@@ -271,7 +335,26 @@ mod body {
         }
     }
     
+    fn mix(a: ColorWrap, b: ColorWrap, weight: f32) -> ColorWrap {
+        let (a, b): (Color, Color) = (a.into(), b.into());
+        let mix = |x: f32, y: f32| x * (1.0 - weight) + y * weight;
+        ColorWrap { r: mix(a.r, b.r), g: mix(a.g, b.g), b: mix(a.b, b.b), a: mix(a.a, b.a) }
+    }
+    
     impl Body {
+        /// A body made while running, colored like the next one loaded would be.
+        pub fn new(id: BodyId, pos: Vector, speed: Vector, mass: Num) -> Self {
+            // Drop whatever a removed body of the same id left behind.
+            get_body_info(|info| info.lock().unwrap().insert(id, HashMap::new()));
+            Body { id, pos, speed, mass, test_particle: false, color: color(), trail_color: trail_color() }
+        }
+        
+        /// Mix in the colors of `other`, which makes up `weight` of the body.
+        pub fn blend(&mut self, other: &Body, weight: f32) {
+            self.color = mix(self.color, other.color, weight);
+            self.trail_color = mix(self.trail_color, other.trail_color, weight);
+        }
+        
         pub fn color(&self) -> Color {
             self.color.into()
         }
@@ -285,8 +368,13 @@ mod config {
     use std::collections::HashMap;
     use super::*;
     use super::body::Body;
+    use crate::simulate::Physics;
+    use crate::units::Units;
+    use crate::apps::app::convert::ConverterData;
+    use crate::apps::app::functions::collision::CollisionMode;
+    use crate::body::BodyId;
+    use crate::frame::Frame;
     
-    make_default!(delta, num(0.001), Num);
     make_default!(steps, 20, isize);
     make_default!(step_per_trail, 5, isize);
     make_default!(trail_alpha_loss_rate, 0.98, f32);
@@ -311,14 +399,28 @@ mod config {
     make_default!(window_width, 800, i32);
     make_default!(window_height, 600, i32);
     make_default!(arrow_size, 10., f32);
+    make_default!(velocity_scale, 1., Num);
+    make_default!(history_size, 2000, usize);
     
     #[derive(Debug, Clone, Deserialize, Serialize)]
     pub struct Config {
         pub bodies: Vec<Body>,
-        #[serde(default = "delta")]
-        pub delta: Num,
+        #[serde(flatten)]
+        pub physics: Physics,
         #[serde(default = "steps")]
         pub steps: isize,
+        /// Simulated time to start from, set by snapshots.
+        #[serde(default = "Default::default")]
+        pub time: Num,
+        /// Camera to start with, set by snapshots.
+        pub view: Option<ConverterData>,
+        #[serde(default = "Default::default")]
+        pub frame: Frame,
+        /// Body the camera follows.
+        pub follow: Option<BodyId>,
+        pub units: Option<Units>,
+        #[serde(default = "Default::default")]
+        pub collision: CollisionMode,
         #[serde(default = "step_per_trail")]
         pub step_per_trail: isize,
         #[serde(default = "trail_alpha_loss_rate")]
@@ -371,6 +473,12 @@ mod config {
         pub window_height: i32,
         #[serde(default = "arrow_size")]
         pub arrow_size: f32,
+        /// Length of the velocity handles of the editor, per unit of speed.
+        #[serde(default = "velocity_scale")]
+        pub velocity_scale: Num,
+        /// Keyframes kept for the timeline, one per frame.
+        #[serde(default = "history_size")]
+        pub history_size: usize,
         
         #[serde(flatten)]
         pub fields: HashMap<String, AttrValue>,
@@ -1,7 +1,8 @@
 use crate::num::{Num, num};
 use crate::vector::{Vector, Vector2D};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ConverterData {
     pub center: Vector,
     pub theta_x: Num,
@@ -31,6 +32,14 @@ impl Converter {
         (Vector2D::new(-v.dot_prod(&self.x), v.dot_prod(&self.y)), v.dot_prod(&self.z))
     }
     
+    /// The point `convert` puts at `v`, `depth` into the screen.
+    pub fn unconvert(&self, v: Vector2D, depth: Num) -> Vector {
+        // The axes are orthogonal, X and Y scaled by the unit length.
+        self.x * (-v.x() / self.x.dot_prod(&self.x))
+            + self.y * (v.y() / self.y.dot_prod(&self.y))
+            + self.z * depth
+    }
+    
     pub fn x_axis(&self) -> &Vector {
         &self.x
     }
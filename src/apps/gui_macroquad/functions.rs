@@ -1,11 +1,16 @@
 pub mod collision;
+pub mod details;
+pub mod diagnostics;
+pub mod editor;
+pub mod events;
+pub mod timeline;
 
 use crate::body::BodyId;
 use macroquad::prelude::*;
 use crate::body::BodyLike;
 use crate::controller::IterStatus;
 use crate::num::{Num, num};
-use super::basic::{AppContext, default_font, Command};
+use super::basic::{AppContext, default_font, Command, Components};
 use super::config::{Config, AttrValue, Body};
 #[allow(unused_imports)]
 use super::ui::{draw_mask, draw_3d_point, draw_3d_arrow, draw_3d_line};
@@ -1,6 +1,28 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use serde::{Deserialize, Serialize};
 use crate::apps::app::basic::Command::AddMessage;
+use crate::vector::Vector;
 use super::*;
 
+/// Entries shown in the collision log overlay, the latest ones.
+const LOG_LINES: usize = 20;
+
+/// What happens when two bodies touch, set by the `collision` config key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionMode {
+    /// Stop the simulation and show the collision.
+    #[default]
+    Pause,
+    /// Replace both with a single body, conserving mass and momentum.
+    Merge,
+    /// Bounce off each other as hard spheres, see `restitution`.
+    Bounce,
+    /// Let bodies pass through each other, until detection is turned on with [C].
+    Ignore,
+}
+
 #[derive(Debug, Copy, Clone)]
 enum Status {
     None,
@@ -8,29 +30,41 @@ enum Status {
 }
 
 
-#[derive(Debug, Copy, Clone)]
+/// A contact between two bodies.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Record {
+    pub time: Num,
+    pub this: BodyId,
+    pub other: BodyId,
+    /// Speed of `other` relative to `this`.
+    pub speed: Num,
+    /// Where the surfaces of the two bodies meet, on the line between their centers.
+    pub point: Vector,
+}
+
+#[derive(Debug, Clone)]
 pub struct CollisionDetect {
     status: Status,
-    on: bool,
+    log: Vec<Record>,
 }
 
 impl Default for CollisionDetect {
     fn default() -> Self {
         Self {
             status: Status::None,
-            on: true,
+            log: Vec::new(),
         }
     }
 }
 
 static mut __DEFAULT_DENSITY: Option<Num> = None;
 
-fn get_density(context: &AppContext, this: &Body) -> Num {
+fn get_density(config: &Config, this: &Body) -> Num {
     if let Some(AttrValue::Num(x)) = this.get_attr(&"density".into()) {
         x
     } else if let Some(x) = unsafe { __DEFAULT_DENSITY } {
         x
-    } else if let Some(AttrValue::Num(x)) = context.config.fields.get(&"default_density".to_string()) {
+    } else if let Some(AttrValue::Num(x)) = config.fields.get(&"default_density".to_string()) {
         let x = *x;
         unsafe { __DEFAULT_DENSITY = Some(x) }
         x
@@ -39,12 +73,122 @@ fn get_density(context: &AppContext, this: &Body) -> Num {
     }
 }
 
+fn radius(config: &Config, this: &Body) -> Num {
+    this.mass() / get_density(config, this)
+}
+
+/// Distance between the centers of two bodies when they touch.
+pub fn contact_distance(config: &Config) -> impl Fn(&Body, &Body) -> Num + '_ {
+    move |this, other| radius(config, this) + radius(config, other)
+}
+
 fn collides_with(context: &AppContext, this: &Body, other: &Body) -> bool {
-    this.pos().distance(*other.pos()) <=
-        this.mass() / get_density(context, this) + other.mass() / get_density(context, other)
+    this.pos().distance(*other.pos()) <= contact_distance(&context.config)(this, other)
+}
+
+/// Share of the normal speed kept in a bounce, 1 for elastic impacts.
+fn get_restitution(context: &AppContext, this: &Body) -> Num {
+    if let Some(AttrValue::Num(x)) = this.get_attr(&"restitution".into()) {
+        x
+    } else if let Some(AttrValue::Num(x)) = context.config.fields.get("default_restitution") {
+        *x
+    } else {
+        num(1)
+    }
 }
 
 
+fn record(context: &AppContext, this: &Body, other: &Body) -> Record {
+    let (r1, r2) = (radius(&context.config, this), radius(&context.config, other));
+    let share = if r1 + r2 == num(0) { num(0.5) } else { r1 / (r1 + r2) };
+    Record {
+        time: context.time,
+        this: *this.id(),
+        other: *other.id(),
+        speed: other.speed().distance(*this.speed()),
+        point: *this.pos() + (*other.pos() - *this.pos()) * share,
+    }
+}
+
+/// Every pair of bodies touching each other, and the pair `step` stopped at the contact of, if any.
+fn collisions(context: &mut AppContext) -> Vec<Record> {
+    let mut found = context.controller.collect_each_other(
+        |this, other| {
+            if collides_with(context, this, other) {
+                IterStatus::Done(record(context, this, other))
+            } else {
+                IterStatus::Continue
+            }
+        }
+    );
+    // Just touching, which rounding may have left a hair apart.
+    if let Some((this, other)) = context.contact.take() {
+        if !found.iter().any(|x| (x.this, x.other) == (this, other)) {
+            if let (Some(a), Some(b)) = (context.controller.get_body(this), context.controller.get_body(other)) {
+                found.insert(0, record(context, a, b));
+            }
+        }
+    }
+    found
+}
+
+impl CollisionDetect {
+    fn merge(&mut self, mut context: AppContext) -> AppContext {
+        let mut merged = false;
+        // Merging moves the survivor, which may then touch other bodies.
+        while let Some(record) = collisions(&mut context).first().copied() {
+            // The heavier body survives, keeping its id and attributes.
+            let (this, other) = (record.this, record.other);
+            let mass = |id| *context.controller.get_body(id).unwrap().mass();
+            let (keep, gone) = if mass(this) >= mass(other) { (this, other) } else { (other, this) };
+            let absorbed = context.controller.merge(keep, gone).unwrap();
+            let body = context.controller.get_body_mut(keep).unwrap();
+            let weight = if *body.mass() == num(0) { num(0.5) } else { absorbed.mass() / body.mass() };
+            body.blend(&absorbed, weight as f32);
+            context.add_message(format!("Body {} merged into {} at {:.3}", gone, keep, context.time).into());
+            self.log.push(record);
+            merged = true;
+        }
+        if merged {
+            context.bodies = context.view.parse(&context);
+        }
+        context
+    }
+    
+    fn bounce(&mut self, mut context: AppContext) -> AppContext {
+        for record in collisions(&mut context) {
+            let this = *context.controller.get_body(record.this).unwrap();
+            let other = *context.controller.get_body(record.other).unwrap();
+            // The less elastic body of the two decides.
+            let restitution = get_restitution(&context, &this).min(get_restitution(&context, &other));
+            let contact = contact_distance(&context.config)(&this, &other);
+            // Resting contacts are separated without an impulse, and are not worth a record.
+            if context.controller.bounce(record.this, record.other, restitution, contact) {
+                self.log.push(record);
+            }
+        }
+        context
+    }
+    
+    /// Write the log as CSV, one collision per line.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "time,this,other,speed,x,y,z")?;
+        for Record { time, this, other, speed, point } in &self.log {
+            let (x, y, z) = point.to_tuple();
+            writeln!(out, "{},{},{},{},{},{},{}", time, this, other, speed, x, y, z)?;
+        }
+        out.flush()
+    }
+    
+    fn export(&self) -> String {
+        let path = format!("./collisions_{}.csv", chrono::Local::now().format("%Y%m%d_%H%M%S"));
+        match File::create(&path).and_then(|file| self.write_csv(BufWriter::new(file))) {
+            Err(err) => format!("Failed to export collisions: {}", err),
+            Ok(_) => format!("{} collisions exported: {:?}", self.log.len(), path),
+        }
+    }
+}
+
 #[allow(unused_variables)]
 impl Function for CollisionDetect {
     fn new(config: &Config) -> Self {
@@ -52,10 +196,17 @@ impl Function for CollisionDetect {
     }
     
     fn update(&mut self, mut context: AppContext) -> AppContext {
-        if !self.on {
+        if !context.stop_on_contact {
             return context;
         }
         
+        // Read on each update, as loading a snapshot may change it.
+        match context.config.collision {
+            CollisionMode::Merge => return self.merge(context),
+            CollisionMode::Bounce => return self.bounce(context),
+            CollisionMode::Pause | CollisionMode::Ignore => {}
+        }
+        
         if let Status::Detected(..) = self.status {
             if context.running {
                 // Forced
@@ -64,25 +215,20 @@ impl Function for CollisionDetect {
             return context;
         }
         
-        let result = context.controller.with_each_other(
-            |this, other| {
-                return if collides_with(&context, this, other) {
-                    IterStatus::Done((*this.id(), *other.id()))
-                } else {
-                    IterStatus::Continue
-                };
-            }
-        );
+        let result = collisions(&mut context);
         
-        if let Some((this, other)) = result {
-            context.add_message(
-                format!(
-                    "Body {} and {} collided at {:.3}",
-                    this, other, context.time
-                ).into()
-            );
+        if let Some(first) = result.first() {
+            for Record { this, other, .. } in &result {
+                context.add_message(
+                    format!(
+                        "Body {} and {} collided at {:.3}",
+                        this, other, context.time
+                    ).into()
+                );
+            }
             context.running = false;
-            self.status = Status::Detected(this, other);
+            self.status = Status::Detected(first.this, first.other);
+            self.log.extend(result);
         } else {
             self.status = Status::None;
         }
@@ -106,14 +252,37 @@ impl Function for CollisionDetect {
                 },
             );
         }
+        
+        if context.ui_status.is_on(Components::CollisionLog) {
+            // Right Up
+            let size = context.config.instruction_font_size;
+            let mut lines = vec![format!("collisions: {}", self.log.len())];
+            lines.extend(self.log.iter().rev().take(LOG_LINES).map(|x| format!(
+                "{:.3}  {} x {}  v {:.3}  at {:#}", x.time, x.this, x.other, x.speed, x.point
+            )));
+            for (i, line) in lines.iter().enumerate() {
+                draw_text_ex(
+                    line,
+                    screen_width() - size as f32 * line.len() as f32 / 1.5,
+                    (size as f32 + 2.0) * (i + 1) as f32,
+                    TextParams {
+                        font: context.instruction_font(),
+                        font_size: size,
+                        color: context.config.instruction_font_color.into(),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
     }
     
     fn event(&mut self, context: &AppContext) -> Option<Command> {
         if is_key_released(KeyCode::C) {
-            self.on = !self.on;
-            Some(AddMessage(
-                format!("Collision Detect turned {}", if self.on { "on" } else { "off" }).into()
-            ))
+            Some(Command::SetCollisionDetect(!context.stop_on_contact))
+        } else if is_key_released(KeyCode::O) {
+            Some(Command::ToggleUI(Components::CollisionLog))
+        } else if is_key_released(KeyCode::F6) {
+            Some(AddMessage(self.export().into()))
         } else {
             None
         }
@@ -122,7 +291,88 @@ impl Function for CollisionDetect {
     fn make_help(&self, context: &AppContext, help: &mut Vec<(String, String)>) {
         help.push((
             "C".into(),
-            format!("{} Collision Detect", if self.on { "Turn off" } else { "Turn on" })
-        ))
+            format!("{} Collision Detect", if context.stop_on_contact { "Turn off" } else { "Turn on" })
+        ));
+        help.push((
+            "O".into(),
+            format!("{} Collision Log", if context.ui_status.is_on(Components::CollisionLog) { "Hide" } else { "Show" })
+        ));
+        help.push(("F6".into(), "Export Collision Log".into()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::apps::app::new_context;
+    use crate::config::parse_config;
+    use super::*;
+    
+    // Colored, so as not to take the default colors from bodies of other tests.
+    const CONFIG: &str = r#"
+        collision = "merge"
+        
+        [[bodies]]
+        id = 0
+        pos = [0, 0, 0]
+        mass = 1
+        color = { r = 1, g = 1, b = 1, a = 1 }
+        
+        [[bodies]]
+        id = 1
+        pos = [0.15, 0, 0]
+        mass = 1
+        color = { r = 1, g = 1, b = 1, a = 1 }
+        
+        [[bodies]]
+        id = 2
+        pos = [0.46, 0, 0]
+        mass = 2
+        color = { r = 1, g = 1, b = 1, a = 1 }
+        
+        [[bodies]]
+        id = 3
+        pos = [10, 0, 0]
+        speed = [1, 0, 0]
+        mass = 2
+        color = { r = 1, g = 1, b = 1, a = 1 }
+        
+        [[bodies]]
+        id = 4
+        pos = [20, 0, 0]
+        speed = [-1, 0, 0]
+        mass = 2
+        color = { r = 1, g = 1, b = 1, a = 1 }
+    "#;
+    
+    #[test]
+    fn test_collision_log() {
+        let config: Config = parse_config(CONFIG).unwrap();
+        let (mut context, _) = new_context(config, None, num(320), num(240));
+        let mut detect = CollisionDetect::new(&context.config);
+        
+        // Body 1 merges into 0, which then reaches body 2.
+        context.time = num(1);
+        context = detect.update(context);
+        assert_eq!(context.controller.len(), 3);
+        
+        // Bodies 3 and 4 run into each other.
+        context.config.collision = CollisionMode::Bounce;
+        context.time = num(2);
+        *context.controller.get_body_mut(4).unwrap().pos_mut() = Vector::new(10.25, 0, 0);
+        context = detect.update(context);
+        assert_eq!(context.controller.len(), 3);
+        
+        let pairs: Vec<_> = detect.log.iter().map(|x| (x.time, x.this, x.other)).collect();
+        assert_eq!(pairs, vec![(num(1), 0, 1), (num(1), 0, 2), (num(2), 3, 4)]);
+        
+        let mut csv = Vec::new();
+        detect.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "time,this,other,speed,x,y,z");
+        assert_eq!(lines[1], "1,0,1,0,0.075,0,0");
+        assert!(lines[2].starts_with("1,0,2,0,"));
+        assert_eq!(lines[3], "2,3,4,2,10.125,0,0");
     }
 }
@@ -0,0 +1,95 @@
+use crate::apps::app::ui::to_f32;
+use super::*;
+
+/// Share of the screen height above the detail panel.
+const PANEL_TOP: f32 = 0.3;
+
+/// Highlights the selected body and lists its state, distances and orbit.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Details;
+
+fn lines(context: &AppContext, id: BodyId) -> Option<Vec<String>> {
+    let controller = &context.controller;
+    let body = controller.get_body(id)?;
+    let mut lines = vec![
+        format!("body {}{}", id, if body.is_test_particle() { " (test particle)" } else { "" }),
+        format!("mass: {:.6}", body.mass()),
+        format!("position: {:#}", body.pos()),
+        format!("velocity: {:#} |{:.4}|", body.speed(), body.speed().module()),
+    ];
+    if let Some(acc) = controller.acceleration(id) {
+        lines.push(format!("acceleration: {:#} |{:.4e}|", acc, acc.module()));
+    }
+    
+    let others: Vec<&Body> = controller.iter().filter(|x| *x.id() != id && !x.is_test_particle()).collect();
+    if !others.is_empty() {
+        lines.push("distance:".into());
+        lines.extend(others.iter().map(|x| format!("  to {}: {:.4}", x.id(), x.pos().distance(*body.pos()))));
+    }
+    
+    let around = match body.get_attr(&"around".into()) {
+        Some(AttrValue::USize(x)) => Some(x),
+        _ => controller.primary_of(id),
+    };
+    if let Some((around, elements)) = around.and_then(|x| Some((x, controller.elements(id, x)?))) {
+        lines.extend([
+            format!("orbit around {}:", around),
+            format!("  a: {:.4}  e: {:.4}", elements.a, elements.e),
+            format!("  i: {:.2}°  ω: {:.2}°  Ω: {:.2}°",
+                    elements.i.to_degrees(), elements.omega.to_degrees(), elements.node.to_degrees()),
+            format!("  ν: {:.2}°", elements.nu.to_degrees()),
+        ]);
+    }
+    Some(lines)
+}
+
+#[allow(unused_variables)]
+impl Function for Details {
+    fn new(config: &Config) -> Self {
+        Default::default()
+    }
+    
+    fn draw(&self, context: &AppContext) {
+        let Some(id) = context.selected else {
+            return;
+        };
+        let Some(lines) = lines(context, id) else {
+            return;
+        };
+        
+        if let Some((point, _)) = context.bodies.iter().find(|(_, x)| *x == id) {
+            draw_circle_lines(
+                to_f32(context.view.get_x(point)),
+                to_f32(context.view.get_y(point)),
+                to_f32(point.radius) + 6.0,
+                1.5,
+                Color { a: 0.9, ..YELLOW },
+            );
+        }
+        
+        // Right Middle
+        let size = context.config.instruction_font_size;
+        let width = lines.iter().map(|x| x.chars().count()).max().unwrap_or(0) as f32 * size as f32 / 1.8;
+        let height = (size as f32 + 2.0) * lines.len() as f32;
+        let (left, top) = (screen_width() - width - 10.0, screen_height() * PANEL_TOP);
+        draw_rectangle(left - 6.0, top - size as f32, width + 12.0, height + 8.0, Color { a: 0.6, ..BLACK });
+        for (i, line) in lines.iter().enumerate() {
+            draw_text_ex(
+                line,
+                left,
+                top + (size as f32 + 2.0) * i as f32,
+                TextParams {
+                    font: context.instruction_font(),
+                    font_size: size,
+                    color: context.config.instruction_font_color.into(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    
+    fn make_help(&self, context: &AppContext, help: &mut Vec<(String, String)>) {
+        help.push(("Click".into(), "Select Body".into()));
+        help.push(("Double Click".into(), "Follow Body".into()));
+    }
+}
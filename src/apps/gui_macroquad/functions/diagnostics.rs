@@ -0,0 +1,48 @@
+use crate::apps::app::basic::Command::{AddMessage, MultiCommand};
+use super::*;
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Diagnostics;
+
+#[allow(unused_variables)]
+impl Function for Diagnostics {
+    fn new(config: &Config) -> Self {
+        Default::default()
+    }
+    
+    fn event(&mut self, context: &AppContext) -> Option<Command> {
+        if is_key_released(KeyCode::E) {
+            let on = !context.ui_status.is_on(Components::Diagnostics);
+            Some(MultiCommand(vec![
+                Command::ToggleUI(Components::Diagnostics),
+                AddMessage(format!("Diagnostics turned {}", if on { "on" } else { "off" }).into()),
+            ]))
+        } else {
+            None
+        }
+    }
+    
+    fn make_help(&self, context: &AppContext, help: &mut Vec<(String, String)>) {
+        help.push((
+            "E".into(),
+            format!("{} Diagnostics", if context.ui_status.is_on(Components::Diagnostics) { "Hide" } else { "Show" })
+        ))
+    }
+    
+    fn make_title(&self, context: &AppContext, title: &mut Vec<String>) {
+        if !context.ui_status.is_on(Components::Diagnostics) {
+            return;
+        }
+        
+        let controller = &context.controller;
+        let drift = controller.drift();
+        title.extend([
+            format!("energy: {:.6} (K {:.4}, U {:.4})",
+                    controller.total_energy(), controller.kinetic_energy(), controller.potential_energy()),
+            format!("momentum: {:#}", controller.momentum()),
+            format!("angular momentum: {:#}", controller.angular_momentum()),
+            format!("center of mass: {:#} v {:#}", controller.center_of_mass(), controller.center_of_mass_speed()),
+            format!("drift: E {:.2e}, P {:.2e}, L {:.2e}", drift.energy, drift.momentum, drift.angular_momentum),
+        ]);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::apps::app::basic::Command::{AddMessage, MultiCommand};
+use crate::apps::app::{body_at, PICK_RADIUS};
+use crate::apps::app::canvas::Screen;
+use crate::vector::{Vector, Vector2D};
+use super::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Drag {
+    /// Moving a body, kept at the depth it was grabbed at.
+    Body(BodyId, Num),
+    /// Moving the tip of the velocity handle of a body.
+    Velocity(BodyId, Num),
+}
+
+/// Edit mode: keeps the simulation paused while bodies are moved, sped up, added and removed with the mouse.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    /// Mass of the bodies added with a right click.
+    spawn_mass: Num,
+    drag: Option<Drag>,
+}
+
+fn mouse() -> Vector2D {
+    let (x, y) = mouse_position();
+    Vector2D::new(x, y)
+}
+
+/// Where the velocity handle of `body` points to.
+fn tip(context: &AppContext, body: &Body) -> Vector {
+    *body.pos() + *body.speed() * context.config.velocity_scale
+}
+
+impl Editor {
+    /// What a press at the mouse grabs: the tip of a velocity handle, a body,
+    /// or with Shift held the velocity of a body, handy when it is too slow for its handle to show.
+    fn grab(&self, context: &AppContext) -> Option<Drag> {
+        let view = &context.view;
+        let pos = mouse();
+        for body in context.controller.iter().filter(|x| !x.is_test_particle()) {
+            let (center, _) = view.to_screen(*body.pos());
+            let (tip, depth) = view.to_screen(tip(context, body));
+            if tip.distance(pos) <= num(PICK_RADIUS) && tip.distance(center) > num(PICK_RADIUS) {
+                return Some(Drag::Velocity(*body.id(), depth));
+            }
+        }
+        
+        let id = body_at(context, pos)?;
+        let (_, depth) = view.to_screen(*context.controller.get_body(id)?.pos());
+        Some(if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+            Drag::Velocity(id, depth)
+        } else {
+            Drag::Body(id, depth)
+        })
+    }
+    
+    fn dragged(&self, context: &AppContext, drag: Drag) -> Option<Command> {
+        let view = &context.view;
+        match drag {
+            Drag::Body(id, depth) => Some(Command::MoveBody(id, view.unproject(mouse(), depth))),
+            Drag::Velocity(id, depth) => {
+                let body = context.controller.get_body(id)?;
+                let speed = (view.unproject(mouse(), depth) - *body.pos()) / context.config.velocity_scale;
+                Some(Command::SetSpeed(id, speed))
+            }
+        }
+    }
+}
+
+#[allow(unused_variables)]
+impl Function for Editor {
+    fn new(config: &Config) -> Self {
+        let spawn_mass = config.bodies.iter()
+            .map(|x| *x.mass())
+            .filter(|&x| x > num(0))
+            .reduce(Num::min)
+            .unwrap_or(num(1));
+        Self { spawn_mass, drag: None }
+    }
+    
+    fn draw(&self, context: &AppContext) {
+        if !context.editing {
+            return;
+        }
+        
+        let view = &context.view;
+        let mut screen = Screen::new(context);
+        for body in context.controller.iter().filter(|x| !x.is_test_particle() && !x.speed().is_zero()) {
+            draw_3d_arrow(
+                context,
+                &mut screen,
+                view.to_frame(*body.pos()),
+                view.to_frame(tip(context, body)),
+                1.5,
+                Color { a: 0.8, ..body.color() },
+            );
+        }
+    }
+    
+    fn event(&mut self, context: &AppContext) -> Option<Command> {
+        if is_key_released(KeyCode::G) {
+            self.drag = None;
+            return Some(Command::SetEditing(!context.editing));
+        }
+        if !context.editing {
+            self.drag = None;
+            return None;
+        }
+        if context.running {
+            return Some(Command::SetRunning(false));
+        }
+        
+        if let Some(drag) = self.drag {
+            // The release still moves to where the mouse ends up.
+            if !is_mouse_button_down(MouseButton::Left) {
+                self.drag = None;
+            }
+            return self.dragged(context, drag);
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            self.drag = self.grab(context);
+            let command = self.dragged(context, self.drag?)?;
+            // Grabbing a body selects it too, for [Delete].
+            return Some(match self.drag {
+                Some(Drag::Body(id, _)) if context.selected != Some(id) => {
+                    MultiCommand(vec![Command::Select(Some(id)), command])
+                }
+                _ => command,
+            });
+        }
+        if is_mouse_button_pressed(MouseButton::Right) {
+            return Some(Command::Spawn(context.view.unproject(mouse(), num(0)), self.spawn_mass));
+        }
+        
+        if is_key_released(KeyCode::LeftBracket) || is_key_released(KeyCode::RightBracket) {
+            self.spawn_mass *= if is_key_released(KeyCode::LeftBracket) { num(0.5) } else { num(2) };
+            Some(AddMessage(format!("Spawn mass: {}", self.spawn_mass).into()))
+        } else if is_key_released(KeyCode::Delete) {
+            context.selected.map(Command::RemoveBody)
+        } else if is_key_released(KeyCode::F2) {
+            Some(Command::SaveScenario)
+        } else {
+            None
+        }
+    }
+    
+    fn make_help(&self, context: &AppContext, help: &mut Vec<(String, String)>) {
+        help.push(("G".into(), format!("{} Edit Mode", if context.editing { "Leave" } else { "Enter" })));
+        if !context.editing {
+            return;
+        }
+        
+        help.extend([
+            ("Drag".into(), "Move Body".into()),
+            ("Drag Arrow".into(), "Set Velocity".into()),
+            ("Shift Drag".into(), "Set Velocity".into()),
+            ("Right Click".into(), "Add Body".into()),
+            ("[/]".into(), "Halve/Double Spawn Mass".into()),
+            ("Delete".into(), "Remove Selected Body".into()),
+            ("F2".into(), "Save Scenario".into()),
+        ]);
+    }
+    
+    fn make_title(&self, context: &AppContext, title: &mut Vec<String>) {
+        if context.editing {
+            title.push(format!("editing, spawn mass: {}", self.spawn_mass));
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use crate::events::Detector;
+use super::*;
+
+/// Shows the events of the `[events]` config table as messages.
+#[derive(Debug, Clone)]
+pub struct Events {
+    detector: Detector,
+}
+
+#[allow(unused_variables)]
+impl Function for Events {
+    fn new(config: &Config) -> Self {
+        Self { detector: Detector::new(config.physics.events) }
+    }
+    
+    fn update(&mut self, mut context: AppContext) -> AppContext {
+        // A loaded snapshot may come with other events to look for.
+        if self.detector.settings() != &context.config.physics.events {
+            self.detector = Detector::new(context.config.physics.events);
+        }
+        if !self.detector.settings().is_on() {
+            return context;
+        }
+        
+        for event in self.detector.check(&context.controller) {
+            context.add_message(event.to_string().into());
+        }
+        context
+    }
+}
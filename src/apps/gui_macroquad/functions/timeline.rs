@@ -0,0 +1,132 @@
+use crate::apps::app::basic::Command::{AddMessage, MultiCommand};
+use crate::apps::app::basic::Components;
+use crate::apps::app::ui::to_f32;
+use crate::history::History;
+use super::*;
+
+/// Distance of the timeline to the bottom of the screen.
+const BOTTOM: f32 = 8.0;
+const HEIGHT: f32 = 4.0;
+/// Presses this close above or below the timeline still grab it.
+const GRAB_MARGIN: f32 = 8.0;
+
+/// Records the run going forward, and lets it be scrubbed back to any recorded time
+/// by dragging the timeline at the bottom of the screen. Running on from there starts a new branch.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    history: History<Body>,
+    scrubbing: bool,
+}
+
+/// Left and right ends of the timeline, and its height on the screen.
+fn bounds() -> (f32, f32, f32) {
+    (screen_width() * 0.25, screen_width() * 0.75, screen_height() - BOTTOM)
+}
+
+impl Timeline {
+    /// Where `time` is on the timeline.
+    fn x(&self, time: Num) -> Option<f32> {
+        let (first, last) = self.history.span()?;
+        let (left, right, _) = bounds();
+        let share = if last > first { (time - first) / (last - first) } else { num(1) };
+        Some(left + (right - left) * to_f32(share.clamp(num(0), num(1))))
+    }
+    
+    /// The time at `x` on the timeline.
+    fn time(&self, x: f32) -> Option<Num> {
+        let (first, last) = self.history.span()?;
+        let (left, right, _) = bounds();
+        let share = ((x - left) / (right - left)).clamp(0.0, 1.0);
+        Some(first + (last - first) * share as Num)
+    }
+    
+    /// Go back to `time`, or to the closest recorded time, pausing there.
+    fn jump(&self, time: Num) -> Option<Command> {
+        let keyframe = self.history.nearest(time)?;
+        Some(MultiCommand(vec![Command::SetRunning(false), Command::Restore(keyframe.clone())]))
+    }
+}
+
+#[allow(unused_variables)]
+impl Function for Timeline {
+    fn new(config: &Config) -> Self {
+        Self { history: History::new(config.history_size), scrubbing: false }
+    }
+    
+    fn update(&mut self, mut context: AppContext) -> AppContext {
+        // Running backward does not retrace the recorded run, so it is not recorded.
+        if context.steps <= 0 {
+            return context;
+        }
+        
+        if self.history.record(&context.controller) > 0 {
+            context.add_message(format!("New branch from time {:.3}", context.time).into());
+        }
+        context
+    }
+    
+    fn draw(&self, context: &AppContext) {
+        if self.history.len() < 2 || !context.ui_status.is_on(Components::UI) {
+            return;
+        }
+        
+        let (left, right, y) = bounds();
+        let Some(now) = self.x(context.time) else {
+            return;
+        };
+        draw_rectangle(left, y - HEIGHT / 2.0, right - left, HEIGHT, Color { a: 0.3, ..GRAY });
+        draw_rectangle(left, y - HEIGHT / 2.0, now - left, HEIGHT, Color { a: 0.8, ..LIGHTGRAY });
+        draw_circle(now, y, HEIGHT * if self.scrubbing { 2.0 } else { 1.5 }, WHITE);
+        if self.scrubbing {
+            draw_text_ex(
+                &format!("{:.3}", context.time),
+                now - 20.0,
+                y - HEIGHT * 3.0,
+                TextParams {
+                    font: context.instruction_font(),
+                    font_size: context.config.instruction_font_size,
+                    color: context.config.instruction_font_color.into(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    
+    fn event(&mut self, context: &AppContext) -> Option<Command> {
+        if self.history.is_empty() {
+            // The first update comes after the first step.
+            self.history.record(&context.controller);
+        }
+        
+        let (x, y) = mouse_position();
+        if is_mouse_button_pressed(MouseButton::Left) && self.history.len() >= 2 {
+            let (left, right, bar) = bounds();
+            self.scrubbing = (left..=right).contains(&x) && (y - bar).abs() <= GRAB_MARGIN;
+        }
+        if self.scrubbing {
+            if !is_mouse_button_down(MouseButton::Left) {
+                self.scrubbing = false;
+            }
+            return self.jump(self.time(x)?);
+        }
+        
+        if is_key_released(KeyCode::Comma) || is_key_released(KeyCode::Period) {
+            let offset = if is_key_released(KeyCode::Comma) { -1 } else { 1 };
+            let keyframe = self.history.step(context.time, offset)?;
+            self.jump(keyframe.time)
+        } else if is_key_released(KeyCode::K) {
+            let (first, last) = self.history.span()?;
+            Some(AddMessage(format!(
+                "{} keyframes from time {:.3} to {:.3}", self.history.len(), first, last,
+            ).into()))
+        } else {
+            None
+        }
+    }
+    
+    fn make_help(&self, context: &AppContext, help: &mut Vec<(String, String)>) {
+        help.push(("Drag Timeline".into(), "Go Back in Time".into()));
+        help.push((",/.".into(), "Previous/Next Keyframe".into()));
+        help.push(("K".into(), "Show Recorded Time".into()));
+    }
+}
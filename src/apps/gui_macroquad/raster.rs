@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+use anyhow::{anyhow, Context};
+use ::image::imageops::{self, FilterType};
+use fontdue::{Font, FontSettings, Metrics};
+use macroquad::color::Color;
+use crate::config::EResult;
+use super::canvas::{Canvas, FontKind, TextStyle};
+use super::config::Config;
+
+/// A character as rasterized, with its coverage of each pixel.
+type Glyph = (Metrics, Vec<u8>);
+
+/// Draws in memory, for machines without a GPU nor a display, to RGBA pixels in rows from the top.
+pub struct Raster {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    /// The background picture, scaled to the raster.
+    background: Vec<u8>,
+    tooltip_font: Font,
+    instruction_font: Font,
+    glyphs: HashMap<(FontKind, u16, char), Rc<Glyph>>,
+}
+
+fn load_font(name: Option<&String>) -> EResult<Font> {
+    let bytes = match name {
+        Some(name) => std::fs::read(name).with_context(|| format!("Failed to read font: {}", name))?,
+        None => include_bytes!("../../res/JetBrains Mono Regular.ttf").to_vec(),
+    };
+    Font::from_bytes(bytes, FontSettings::default()).map_err(|x| anyhow!("Failed to load font: {}", x))
+}
+
+/// How much of the pixel wide span around `x` lies within `from..to`.
+fn overlap(x: f32, from: f32, to: f32) -> f32 {
+    ((x + 0.5).min(to) - (x - 0.5).max(from)).clamp(0.0, 1.0)
+}
+
+/// Pixels from `from` to `to`, within the first `limit`.
+fn span(from: f32, to: f32, limit: u32) -> Range<i32> {
+    (from.floor().max(0.0) as i32)..(to.ceil().min(limit as f32) as i32)
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32, config: &Config) -> EResult<Self> {
+        let background = ::image::load_from_memory_with_format(
+            include_bytes!("../../res/background.png"),
+            ::image::ImageFormat::Png,
+        ).context("Failed to load background")?;
+        let background = imageops::resize(&background.to_rgba8(), width, height, FilterType::Triangle);
+        Ok(Self {
+            width,
+            height,
+            pixels: vec![0; background.len()],
+            background: background.into_raw(),
+            tooltip_font: load_font(config.tooltip_font.as_ref())?,
+            instruction_font: load_font(config.instruction_font.as_ref())?,
+            glyphs: HashMap::new(),
+        })
+    }
+    
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    
+    pub fn save(&self, path: &Path) -> EResult<()> {
+        ::image::save_buffer(path, self.pixels(), self.width, self.height, ::image::ColorType::Rgba8)
+            .with_context(|| format!("Failed to write image: {}", path.display()))
+    }
+    
+    /// Lay `color` over the pixel at `x`, `y`, as far as it is covered.
+    fn blend(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let a = (color.a * coverage).clamp(0.0, 1.0);
+        if a <= 0.0 {
+            return;
+        }
+        
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = &mut self.pixels[i..i + 4];
+        for (dst, src) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            *dst = (src.clamp(0.0, 1.0) * 255.0 * a + *dst as f32 * (1.0 - a)).round() as u8;
+        }
+        pixel[3] = (255.0 * a + pixel[3] as f32 * (1.0 - a)).round() as u8;
+    }
+    
+    fn glyph(&mut self, font: FontKind, size: u16, c: char) -> Rc<Glyph> {
+        let face = match font {
+            FontKind::Tooltip => &self.tooltip_font,
+            FontKind::Instruction => &self.instruction_font,
+        };
+        self.glyphs.entry((font, size, c))
+            .or_insert_with(|| Rc::new(face.rasterize(c, size as f32)))
+            .clone()
+    }
+}
+
+impl Canvas for Raster {
+    fn width(&self) -> f32 {
+        self.width as f32
+    }
+    
+    fn height(&self) -> f32 {
+        self.height as f32
+    }
+    
+    fn background(&mut self) {
+        self.pixels.copy_from_slice(&self.background);
+    }
+    
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        
+        let (ux, uy) = (dx / length, dy / length);
+        let half = thickness / 2.0;
+        let margin = half + 1.0;
+        for y in span(y1.min(y2) - margin, y1.max(y2) + margin, self.height) {
+            for x in span(x1.min(x2) - margin, x1.max(x2) + margin, self.width) {
+                let (px, py) = (x as f32 + 0.5 - x1, y as f32 + 0.5 - y1);
+                let along = px * ux + py * uy;
+                let across = px * uy - py * ux;
+                self.blend(x, y, color, overlap(along, 0.0, length) * overlap(across, -half, half));
+            }
+        }
+    }
+    
+    fn circle(&mut self, x: f32, y: f32, r: f32, color: Color) {
+        for py in span(y - r - 1.0, y + r + 1.0, self.height) {
+            for px in span(x - r - 1.0, x + r + 1.0, self.width) {
+                let distance = (px as f32 + 0.5 - x).hypot(py as f32 + 0.5 - y);
+                self.blend(px, py, color, overlap(distance, -r, r));
+            }
+        }
+    }
+    
+    fn rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        for py in span(y, y + h, self.height) {
+            for px in span(x, x + w, self.width) {
+                let coverage = overlap(px as f32 + 0.5, x, x + w) * overlap(py as f32 + 0.5, y, y + h);
+                self.blend(px, py, color, coverage);
+            }
+        }
+    }
+    
+    fn text(&mut self, text: &str, x: f32, y: f32, style: TextStyle) {
+        let mut pen = x;
+        for c in text.chars() {
+            let glyph = self.glyph(style.font, style.size, c);
+            let (metrics, bitmap) = glyph.as_ref();
+            let left = pen.round() as i32 + metrics.xmin;
+            let top = y.round() as i32 - metrics.ymin - metrics.height as i32;
+            for (i, &coverage) in bitmap.iter().enumerate() {
+                let (gx, gy) = ((i % metrics.width) as i32, (i / metrics.width) as i32);
+                self.blend(left + gx, top + gy, style.color, coverage as f32 / 255.0);
+            }
+            pen += metrics.advance_width;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use macroquad::color::{BLACK, WHITE};
+    use super::*;
+    
+    fn raster(width: u32, height: u32) -> Raster {
+        let config: Config = crate::config::parse_config("bodies = []").unwrap();
+        let mut raster = Raster::new(width, height, &config).unwrap();
+        raster.rectangle(0.0, 0.0, width as f32, height as f32, BLACK);
+        raster
+    }
+    
+    fn pixel(raster: &Raster, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * raster.width as usize + x) * 4;
+        raster.pixels()[i..i + 4].try_into().unwrap()
+    }
+    
+    #[test]
+    fn test_raster_shapes() {
+        let mut raster = raster(32, 32);
+        assert!(raster.pixels().chunks(4).all(|x| x == [0, 0, 0, 255]));
+        
+        raster.circle(8.0, 8.0, 4.0, Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(pixel(&raster, 8, 8), [255, 0, 0, 255]);
+        assert_eq!(pixel(&raster, 8, 14), [0, 0, 0, 255]);
+        // The edge is smoothed.
+        assert!((1..255).contains(&pixel(&raster, 11, 10)[0]));
+        
+        raster.rectangle(16.0, 0.0, 16.0, 16.0, Color { a: 0.5, ..WHITE });
+        assert_eq!(pixel(&raster, 20, 4), [128, 128, 128, 255]);
+        assert_eq!(pixel(&raster, 15, 4), [0, 0, 0, 255]);
+        
+        raster.line(0.0, 24.5, 32.0, 24.5, 1.0, WHITE);
+        assert_eq!(pixel(&raster, 16, 24), [255, 255, 255, 255]);
+        assert_eq!(pixel(&raster, 16, 23), [0, 0, 0, 255]);
+        assert_eq!(pixel(&raster, 16, 25), [0, 0, 0, 255]);
+    }
+    
+    #[test]
+    fn test_raster_text() {
+        let mut raster = raster(64, 24);
+        let style = TextStyle { font: FontKind::Instruction, size: 15, color: WHITE };
+        raster.text("time", 2.0, 16.0, style);
+        let lit: Vec<_> = (0..24).flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(&raster, x, y)[0] > 128)
+            .collect();
+        assert!(!lit.is_empty());
+        // On the baseline, and a little above it.
+        assert!(lit.iter().all(|&(x, y)| (2..40).contains(&x) && (4..=16).contains(&y)), "{:?}", lit);
+    }
+}
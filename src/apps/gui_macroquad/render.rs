@@ -0,0 +1,188 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::task::{Poll, Waker};
+use anyhow::Context;
+use macroquad::texture::Image;
+use crate::config::EResult;
+use crate::num::{abs, min, num, Num};
+use super::basic::{AppContext, Components};
+use super::config::Config;
+use super::functions::Functions;
+use super::raster::Raster;
+use super::{add_trail, advance, fit_view, new_context, ui, update_functions, update_scene};
+
+/// Where and how often to render a scenario.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub duration: Num,
+    pub interval: Num,
+    pub width: u32,
+    pub height: u32,
+    pub output: PathBuf,
+}
+
+#[cfg(feature = "clap")]
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Config file to render
+    #[arg(default_value_t = crate::config::default_config_file())]
+    pub file_name: String,
+    /// Render a built-in scenario instead of the config file
+    #[arg(long, conflicts_with = "file_name")]
+    pub preset: Option<String>,
+    /// Simulated time to render
+    #[arg(short, long)]
+    pub duration: Num,
+    /// Simulated time between two frames
+    #[arg(short, long)]
+    pub interval: Num,
+    /// Width of the frames in pixels
+    #[arg(long, default_value_t = 1280)]
+    pub width: u32,
+    /// Height of the frames in pixels
+    #[arg(long, default_value_t = 720)]
+    pub height: u32,
+    /// Directory to write the frames into, as frame_00000.png, frame_00001.png, ...
+    #[arg(short, long, default_value = "frames")]
+    pub output: String,
+}
+
+#[cfg(feature = "clap")]
+impl Args {
+    pub fn run(&self) -> EResult<()> {
+        let config: Config = crate::config::load(&self.file_name, self.preset.as_deref())?;
+        anyhow::ensure!(self.duration >= num(0), "Duration must not be negative");
+        anyhow::ensure!(self.interval > num(0), "Frame interval must be positive");
+        anyhow::ensure!(self.width > 0 && self.height > 0, "Frames must not be empty");
+        let settings = Settings {
+            duration: self.duration,
+            interval: self.interval,
+            width: self.width,
+            height: self.height,
+            output: PathBuf::from(&self.output),
+        };
+        std::fs::create_dir_all(&settings.output)
+            .with_context(|| format!("Failed to create output directory: {}", self.output))?;
+        
+        let frames = block_on(render(config, &settings))?;
+        eprintln!("Rendered {} frames into {}", frames, self.output);
+        Ok(())
+    }
+}
+
+/// Save `image` as read from the GPU, whose rows come bottom up.
+pub fn save_png(image: &Image, path: &Path) -> EResult<()> {
+    let row = image.width as usize * 4;
+    let bytes: Vec<u8> = image.bytes.chunks(row).rev().flatten().copied().collect();
+    ::image::save_buffer(path, &bytes, image.width as u32, image.height as u32, ::image::ColorType::Rgba8)
+        .with_context(|| format!("Failed to write image: {}", path.display()))
+}
+
+/// Run on from the time of `context` up to `time`, laying trails as the window does.
+async fn run_until(mut context: AppContext, functions: &mut Functions, time: Num, steps: &mut isize) -> AppContext {
+    let adaptive = context.config.physics.integrator.is_adaptive();
+    let step_per_trail = context.config.step_per_trail.max(1);
+    // Adaptive integrators pick their own sub steps, and lay a trail each step.
+    let (delta, per_trail) = if adaptive {
+        (context.config.physics.delta * step_per_trail as Num, 1)
+    } else {
+        (context.config.physics.delta, step_per_trail)
+    };
+    let precision = abs(delta) * num(1e-9);
+    while context.time < time - precision {
+        let delta = min(delta, time - context.time);
+        if !advance(&mut context, delta) {
+            // Stopped at a contact, for the collision function to deal with.
+            context = update_functions(context, functions).await;
+        }
+        *steps += 1;
+        if *steps % per_trail == 0 {
+            // Trails are laid where the bodies are drawn, which frames far apart would leave behind.
+            context.view.follow(&context.controller);
+            context.bodies = context.view.parse(&context);
+            context = add_trail(context).await;
+        }
+    }
+    context
+}
+
+/// Run the steps of the app, which never wait, without the executor of a window.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match pin!(future).poll(&mut std::task::Context::from_waker(Waker::noop())) {
+        Poll::Ready(x) => x,
+        Poll::Pending => unreachable!("Steps of the app do not wait"),
+    }
+}
+
+/// The app as its first frame shows it, for frames of `width` by `height`, without a window.
+async fn start(config: Config, width: u32, height: u32) -> (AppContext, Functions) {
+    let (mut context, mut functions) = new_context(config, None, width as Num, height as Num);
+    context.ui_status.toggle(Components::Message);
+    context = update_scene(context).await;
+    context = update_functions(context, &mut functions).await;
+    if context.config.view.is_none() && fit_view(&mut context) {
+        context.points.clear();
+        context = update_scene(context).await;
+    }
+    (context, functions)
+}
+
+/// Render the frames, returning how many were written.
+async fn render(config: Config, settings: &Settings) -> EResult<usize> {
+    let mut raster = Raster::new(settings.width, settings.height, &config)?;
+    let (mut context, mut functions) = start(config, settings.width, settings.height).await;
+    
+    let start = context.time;
+    let frames = (settings.duration / settings.interval + num(1e-9)).floor() as usize + 1;
+    let mut steps = 0;
+    for k in 0..frames {
+        if k > 0 {
+            context.running = true;
+            context = run_until(context, &mut functions, start + settings.interval * k as Num, &mut steps).await;
+            context = update_scene(context).await;
+            context = update_functions(context, &mut functions).await;
+        }
+        
+        ui::draw(&context, &functions, &mut raster);
+        raster.save(&settings.output.join(format!("frame_{:05}.png", k)))?;
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/figure8.png");
+    
+    /// The figure eight preset, drawn half way through its period.
+    fn figure8() -> Raster {
+        let config: Config = crate::config::parse_config(crate::presets::find("figure8").unwrap().config).unwrap();
+        let mut raster = Raster::new(320, 240, &config).unwrap();
+        block_on(async {
+            let (mut context, mut functions) = start(config, 320, 240).await;
+            context = run_until(context, &mut functions, num(3.163), &mut 0).await;
+            context = update_scene(context).await;
+            ui::draw(&context, &functions, &mut raster);
+        });
+        raster
+    }
+    
+    /// Set UPDATE_GOLDEN to write the golden image anew, after changing how the scene looks.
+    #[test]
+    fn test_render_golden() {
+        let raster = figure8();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            raster.save(GOLDEN.as_ref()).unwrap();
+        }
+        
+        let golden = ::image::open(GOLDEN).unwrap().to_rgba8();
+        assert_eq!(golden.dimensions(), (320, 240));
+        let changed = golden.as_raw().chunks(4).zip(raster.pixels().chunks(4))
+            .filter(|(a, b)| a.iter().zip(*b).any(|(a, b)| a.abs_diff(*b) > 16))
+            .count();
+        // Leave room for rounding on other platforms, not for anything missing.
+        assert!(changed <= 20, "{} pixels differ from {}", changed, GOLDEN);
+    }
+}
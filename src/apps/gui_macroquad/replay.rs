@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::config::EResult;
+use super::basic::{AppContext, Command};
+use super::config::Config;
+use super::snapshot;
+
+/// A command, and the frame it was handled on.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Entry {
+    pub frame: u64,
+    pub command: Command,
+}
+
+/// A session as it went: the state it started from, and the commands handled frame by frame,
+/// which played back in order repeat it step for step.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Replay {
+    /// Frames the session lasted.
+    pub frames: u64,
+    #[serde(deserialize_with = "crate::config::deserialize_config")]
+    pub config: Config,
+    #[serde(default)]
+    pub commands: VecDeque<Entry>,
+}
+
+impl Replay {
+    /// Start recording from the state `context` is in.
+    pub fn start(context: &AppContext) -> Self {
+        Self { frames: 0, config: snapshot::make(context), commands: VecDeque::new() }
+    }
+    
+    pub fn record(&mut self, frame: u64, command: &Command) {
+        if command.is_replayed() {
+            self.commands.push_back(Entry { frame, command: command.clone() });
+        }
+    }
+    
+    /// Take out the commands handled up to `frame`, in order.
+    pub fn take(&mut self, frame: u64) -> Vec<Command> {
+        let count = self.commands.iter().take_while(|x| x.frame <= frame).count();
+        self.commands.drain(..count).map(|x| x.command).collect()
+    }
+    
+    pub fn save(&self, path: &str) -> EResult<()> {
+        let content = toml::to_string(self)
+            .context("Failed to serialize replay")?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write replay: {}", path))
+    }
+    
+    /// Load a replay, whose configs are converted as `parse_config` converts a config file.
+    pub fn load(path: &str) -> EResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read replay: {}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse replay: {}", path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::num::num;
+    use crate::vector::Vector;
+    use super::super::basic::Components;
+    use super::super::config::Body;
+    use super::super::functions::Functions;
+    use super::super::render::block_on;
+    use super::super::{handle, new_context, step, update, update_functions};
+    use super::*;
+    
+    /// Frames of the app as the window runs them, with the commands at each frame coming from `input`.
+    async fn run(
+        mut context: AppContext,
+        functions: &mut Functions,
+        frames: u64,
+        mut input: impl FnMut(u64) -> Vec<Command>,
+    ) -> AppContext {
+        while context.frame < frames {
+            for command in input(context.frame) {
+                context = handle(context, command).await;
+            }
+            if context.running {
+                context = step(context).await;
+                context = update(context).await;
+                context = update_functions(context, functions).await;
+            }
+            context.frame += 1;
+        }
+        context
+    }
+    
+    fn state(context: &AppContext) -> (Vec<Body>, u64, bool, bool, bool, isize) {
+        let bodies = context.controller.iter().copied().collect();
+        let ui = context.ui_status.is_on(Components::Diagnostics);
+        (bodies, context.time.to_bits(), context.running, context.stop_on_contact, ui, context.steps)
+    }
+    
+    #[test]
+    fn test_replay_playback() {
+        let config: Config = crate::config::parse_config(crate::presets::find("figure8").unwrap().config).unwrap();
+        let snapshot = Config { time: num(0.5), ..config.clone() };
+        let script = move |frame| match frame {
+            3 => vec![Command::AddSpeed(10), Command::SetCollisionDetect(false)],
+            5 => vec![Command::SetEditing(true), Command::Spawn(Vector::new(1, 1, 0), num(0.1))],
+            6 => vec![Command::MoveBody(0, Vector::new(-1, 0, 0)), Command::SetEditing(false)],
+            8 => vec![Command::ToggleUI(Components::Diagnostics), Command::SetCollisionDetect(true)],
+            10 => vec![Command::LoadConfig(Box::new(snapshot.clone())), Command::SetRunning(true)],
+            12 => vec![Command::AddSpeed(-5)],
+            _ => vec![],
+        };
+        
+        let path = std::env::temp_dir().join(format!("replay_{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        let recorded = block_on(async {
+            let (mut context, mut functions) = new_context(config, None, num(320), num(240));
+            context.recording = Some(Replay::start(&context));
+            let mut context = run(context, &mut functions, 20, script).await;
+            let mut recording = context.recording.take().unwrap();
+            recording.frames = context.frame;
+            recording.save(path).unwrap();
+            context
+        });
+        
+        let mut replay = Replay::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(replay.commands.len(), 11);
+        let played = block_on(async {
+            let (context, mut functions) = new_context(replay.config.clone(), None, num(320), num(240));
+            let frames = replay.frames;
+            run(context, &mut functions, frames, |frame| replay.take(frame)).await
+        });
+        assert_eq!(state(&played), state(&recorded));
+        // The moved body touched another, pausing the run, and the loaded snapshot left out the spawned body.
+        assert_eq!(played.controller.iter().count(), 3);
+        assert!(played.time > num(0.5));
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+use anyhow::Context;
+use crate::config::{parse_config, EResult};
+use crate::controller::Controller;
+use super::basic::AppContext;
+use super::config::Config;
+use super::functions::collision::CollisionMode;
+
+const PREFIX: &str = "snapshot_";
+const SCENARIO_PREFIX: &str = "scenario_";
+const SUFFIX: &str = ".toml";
+
+/// The running state as a config, which resumes where it was taken when loaded.
+pub fn make(context: &AppContext) -> Config {
+    Config {
+        bodies: context.controller.iter().copied().collect(),
+        time: context.controller.total_seconds(),
+        steps: context.steps,
+        view: Some(context.view.data().clone()),
+        frame: context.view.frame(),
+        follow: context.view.target(),
+        ..context.config.clone()
+    }
+}
+
+fn write(config: &Config, prefix: &str) -> EResult<String> {
+    let content = toml::to_string(config)
+        .context("Failed to serialize config")?;
+    let path = format!("./{}{}{}", prefix, chrono::Local::now().format("%Y%m%d_%H%M%S"), SUFFIX);
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write config: {}", path))?;
+    Ok(path)
+}
+
+/// Save a snapshot into the working directory, returning its path.
+pub fn save(context: &AppContext) -> EResult<String> {
+    write(&make(context), PREFIX)
+}
+
+/// Save the bodies as they are into the working directory, as a scenario starting over from time 0.
+pub fn save_scenario(context: &AppContext) -> EResult<String> {
+    write(&Config { time: 0., ..make(context) }, SCENARIO_PREFIX)
+}
+
+/// The most recent snapshot in the working directory.
+pub fn latest() -> Option<PathBuf> {
+    std::fs::read_dir(".").ok()?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.file_name()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| x.starts_with(PREFIX) && x.ends_with(SUFFIX)))
+        .max()
+}
+
+pub fn load(path: &PathBuf) -> EResult<Config> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read snapshot: {}", path.display()))?;
+    parse_config(&content)
+        .with_context(|| format!("Failed to parse snapshot: {}", path.display()))
+}
+
+/// Replace the simulation and the camera with those of `config`, dropping the old trails.
+pub fn restore(context: &mut AppContext, config: Config) {
+    let integrator = config.physics.integrator.build(config.physics.step_control());
+    let mut controller = Controller::new(config.bodies.clone(), integrator, config.physics.gravity());
+    controller.set_time(config.time);
+    context.controller = controller;
+    context.time = config.time;
+    context.steps = config.steps;
+    if let Some(data) = &config.view {
+        context.view.set_data(data.clone());
+    }
+    context.view.set_frame(config.frame);
+    context.view.set_target(config.follow);
+    context.points.clear();
+    context.stop_on_contact = config.collision != CollisionMode::Ignore;
+    context.config = config;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::body::BodyLike;
+    use crate::num::num;
+    use super::super::config::Body;
+    use super::super::render::block_on;
+    use super::super::{new_context, update_functions};
+    use super::*;
+    
+    #[test]
+    fn test_snapshot_round_trip() {
+        let configs = [
+            ("figure8", crate::presets::find("figure8").unwrap().config),
+            // With a [units] table, and bodies placed by their orbits.
+            ("sun_earth_moon", include_str!("../../../examples/sun_earth_moon.toml")),
+            // With test particles, which stay test particles.
+            ("particles", include_str!("../../../examples/particles.toml")),
+        ];
+        for (name, content) in configs {
+            let config: Config = parse_config(content).unwrap();
+            let config_bodies = config.bodies.clone();
+            let (mut context, _) = new_context(config, None, num(320), num(240));
+            for _ in 0..10 {
+                context.controller.update(context.config.physics.delta);
+            }
+            context.steps = 7;
+            
+            let snapshot = make(&context);
+            let loaded: Config = parse_config(&toml::to_string(&snapshot).unwrap()).unwrap();
+            assert_eq!(loaded.bodies, snapshot.bodies, "{}", name);
+            let particles = |bodies: &[Body]| bodies.iter().filter(|x| x.is_test_particle()).count();
+            assert_eq!(particles(&config_bodies) > 0, name == "particles");
+            assert_eq!(particles(&loaded.bodies), particles(&config_bodies), "{}", name);
+            assert_eq!(loaded.time, context.controller.total_seconds(), "{}", name);
+            assert_eq!(loaded.steps, 7);
+            assert_eq!(loaded.collision, snapshot.collision, "{}", name);
+            assert_eq!(loaded.physics.events, snapshot.physics.events, "{}", name);
+            assert_eq!(loaded.physics.gravitational_constant, context.config.physics.gravitational_constant, "{}", name);
+        }
+    }
+    
+    #[test]
+    fn test_snapshot_collision() {
+        let config: Config = parse_config(crate::presets::find("figure8").unwrap().config).unwrap();
+        assert_eq!(config.collision, CollisionMode::Pause);
+        let (mut context, mut functions) = new_context(config, None, num(320), num(240));
+        
+        // Loaded with two bodies on top of each other, which the new mode merges.
+        let mut snapshot = make(&context);
+        *snapshot.bodies[1].pos_mut() = *snapshot.bodies[0].pos();
+        snapshot.collision = CollisionMode::Merge;
+        let loaded: Config = parse_config(&toml::to_string(&snapshot).unwrap()).unwrap();
+        assert_eq!(loaded.collision, CollisionMode::Merge);
+        restore(&mut context, loaded);
+        let context = block_on(update_functions(context, &mut functions));
+        assert_eq!(context.controller.len(), 2);
+        assert!(context.running);
+        
+        let (mut context, _) = new_context(snapshot.clone(), None, num(320), num(240));
+        restore(&mut context, Config { collision: CollisionMode::Ignore, ..snapshot });
+        assert!(!context.stop_on_contact);
+    }
+}
@@ -1,29 +1,30 @@
 use std::collections::HashMap;
 use macroquad::prelude::*;
-use std::default::Default;
 use crate::body::{BodyId, BodyLike};
 use super::basic::{AppContext, Components, Message};
+use super::canvas::{Canvas, FontKind, TextStyle};
+use super::config::AttrValue;
 use crate::num::{Num, PI, num};
 use crate::vector::Vector;
 use super::functions::Functions;
 
 
-fn to_f32(x: Num) -> f32 {
+pub fn to_f32(x: Num) -> f32 {
     x as f32
 }
 
 
 
 macro_rules! draw_text_lines_ex_wrap {
-    ($txt: expr, $pos_x: expr, $pos_y: expr, $step: expr, $params: expr) => {
+    ($canvas: expr, $txt: expr, $pos_x: expr, $pos_y: expr, $step: expr, $style: expr) => {
         let __len = $txt.len();
         if __len <= 1 {
             for __line in $txt {
-                draw_text_ex(
+                $canvas.text(
                     __line.as_str(),
                     $pos_x,
                     $pos_y,
-                    $params,
+                    $style,
                 );
             }
         } else {
@@ -31,11 +32,11 @@ macro_rules! draw_text_lines_ex_wrap {
             let __length = $step / 2.0;
             let __step = 2.0;
             for __line in $txt {
-                draw_text_ex(
+                $canvas.text(
                     __line.as_str(),
                     $pos_x,
                     $pos_y + (__i * __length),
-                    $params,
+                    $style,
                 );
                 __i += __step;
             }
@@ -45,15 +46,15 @@ macro_rules! draw_text_lines_ex_wrap {
 
 
 macro_rules! draw_text_lines_ex_up {
-    ($txt: expr, $pos_x: expr, $pos_y: expr, $step: expr, $params: expr) => {
+    ($canvas: expr, $txt: expr, $pos_x: expr, $pos_y: expr, $step: expr, $style: expr) => {
         let __len = $txt.len();
         if __len <= 1 {
             for __line in $txt {
-                draw_text_ex(
+                $canvas.text(
                     __line.as_str(),
                     $pos_x,
                     $pos_y,
-                    $params,
+                    $style,
                 );
             }
         } else {
@@ -61,11 +62,11 @@ macro_rules! draw_text_lines_ex_up {
             let __length = $step;
             let __step = 1.0;
             for __line in $txt {
-                draw_text_ex(
+                $canvas.text(
                     __line.as_str(),
                     $pos_x,
                     $pos_y + (__i * __length),
-                    $params,
+                    $style,
                 );
                 __i += __step;
             }
@@ -75,15 +76,15 @@ macro_rules! draw_text_lines_ex_up {
 
 #[allow(unused_macros)]
 macro_rules! draw_text_lines_ex_down {
-    ($txt: expr, $pos_x: expr, $pos_y: expr, $step: expr, $params: expr) => {
+    ($canvas: expr, $txt: expr, $pos_x: expr, $pos_y: expr, $step: expr, $style: expr) => {
         let __len = $txt.len();
         if __len <= 1 {
             for __line in $txt {
-                draw_text_ex(
+                $canvas.text(
                     __line.as_str(),
                     $pos_x,
                     $pos_y,
-                    $params,
+                    $style,
                 );
             }
         } else {
@@ -91,11 +92,11 @@ macro_rules! draw_text_lines_ex_down {
             let __length = $step;
             let __step = 1.0;
             for __line in $txt {
-                draw_text_ex(
+                $canvas.text(
                     __line.as_str(),
                     $pos_x,
                     $pos_y + (__i * __length),
-                    $params,
+                    $style,
                 );
                 __i += __step;
             }
@@ -105,40 +106,33 @@ macro_rules! draw_text_lines_ex_down {
 
 
 
-pub async fn draw(context: &AppContext, functions: &Functions) {
-    draw_background(&context);
+pub fn draw(context: &AppContext, functions: &Functions, canvas: &mut impl Canvas) {
+    draw_background(canvas);
     
     if context.ui_status.is_on(Components::Axis) {
-        draw_axis(context).await;
+        draw_axis(context, canvas);
     }
     
-    draw_points(context).await;
+    draw_points(context, canvas);
     
-    draw_ui(context, functions).await;
+    draw_ui(context, functions, canvas);
 }
 
-fn draw_background(context: &AppContext) {
+fn draw_background(canvas: &mut impl Canvas) {
     // clear_background(BLACK);
-    draw_texture_ex(
-        &context.textures.background,
-        0., 0., WHITE,
-        DrawTextureParams {
-            dest_size: Some(Vec2 { x: screen_width(), y: screen_height() }),
-            ..Default::default()
-        },
-    );
-    draw_mask_ex(Color { a: 0.3, ..BLACK });
+    canvas.background();
+    draw_mask_ex(canvas, Color { a: 0.3, ..BLACK });
 }
 
 #[allow(dead_code)]
-pub async fn draw_3d_line(
-    context: &AppContext, from: Vector, to: Vector, thickness: f32, color: Color,
+pub fn draw_3d_line(
+    context: &AppContext, canvas: &mut impl Canvas, from: Vector, to: Vector, thickness: f32, color: Color,
 ) {
     let view = &context.view;
     let center = view.center();
     let from = view.convert(from).0 + center;
     let to = view.convert(to).0 + center;
-    draw_line(
+    canvas.line(
         to_f32(from.x()),
         to_f32(from.y()),
         to_f32(to.x()),
@@ -148,19 +142,19 @@ pub async fn draw_3d_line(
     );
 }
 
-pub async fn draw_3d_arrow(
-    context: &AppContext, from: Vector, to: Vector, thickness: f32, color: Color,
+pub fn draw_3d_arrow(
+    context: &AppContext, canvas: &mut impl Canvas, from: Vector, to: Vector, thickness: f32, color: Color,
 ) {
     let view = &context.view;
     let center = view.center();
     let from = view.convert(from).0 + center;
-    let to_base = view.convert(to).0;
+    let to = view.convert(to).0 + center;
     let size = context.config.arrow_size;
-    let to = to_base + center;
-    let a = to - (to_base + to_base.rotate(PI / num(4))).unit() * size;
-    let b = to - (to_base + to_base.rotate(-PI / num(4))).unit() * size;
+    let dir = to - from;
+    let a = to - (dir + dir.rotate(PI / num(4))).unit() * size;
+    let b = to - (dir + dir.rotate(-PI / num(4))).unit() * size;
     for target in [from, a, b] {
-        draw_line(
+        canvas.line(
             to_f32(target.x()),
             to_f32(target.y()),
             to_f32(to.x()),
@@ -171,14 +165,14 @@ pub async fn draw_3d_arrow(
     }
 }
 
-pub async fn draw_3d_point(
-    context: &AppContext, pos: Vector, r: f32, color: Color,
+pub fn draw_3d_point(
+    context: &AppContext, canvas: &mut impl Canvas, pos: Vector, r: f32, color: Color,
 ) {
     let view = &context.view;
-    let pos = view.convert(pos).0;
-    draw_circle(
-        to_f32(pos.x() + view.half_width()),
-        to_f32(pos.y() + view.half_height()),
+    let pos = view.convert(pos).0 + view.center();
+    canvas.circle(
+        to_f32(pos.x()),
+        to_f32(pos.y()),
         r,
         color,
     );
@@ -188,8 +182,8 @@ pub fn draw_mask(color: Color) {
     draw_rectangle(0., 0., screen_width(), screen_height(), Color { a: 0.4 * color.a, ..color });
 }
 
-pub fn draw_mask_ex(color: Color) {
-    draw_rectangle(0., 0., screen_width(), screen_height(), color);
+pub fn draw_mask_ex(canvas: &mut impl Canvas, color: Color) {
+    canvas.rectangle(0., 0., canvas.width(), canvas.height(), color);
 }
 
 static AXIS: [((Num, Num, Num), Color); 3] = [
@@ -204,27 +198,27 @@ static AXIS_POINTS: [((Num, Num, Num), Color); 0] = [
     // ((0., 0., 1.), BLUE),
 ];
 
-async fn draw_axis(context: &AppContext) {
+fn draw_axis(context: &AppContext, canvas: &mut impl Canvas) {
     // Give up ...
     let view = &context.view;
-    draw_circle(
+    canvas.circle(
         to_f32(view.half_width()),
         to_f32(view.half_height()),
         1.,
         GRAY,
     );
-    draw_3d_point(context, Vector::origin(), 2., GRAY).await;
+    draw_3d_point(context, canvas, Vector::origin(), 2., GRAY);
     for (pos, color) in AXIS {
         let pos = Vector::from_tuple(pos);
-        draw_3d_arrow(context, Vector::origin(), pos, 1., color).await;
+        draw_3d_arrow(context, canvas, Vector::origin(), pos, 1., color);
     }
     for (pos, color) in AXIS_POINTS {
         let pos = Vector::from_tuple(pos);
-        draw_3d_point(context, pos, 2., color).await;
+        draw_3d_point(context, canvas, pos, 2., color);
     }
 }
 
-async fn draw_trails(context: &AppContext) {
+fn draw_trails(context: &AppContext, canvas: &mut impl Canvas) {
     // for point in &context.points {
     //     draw_circle(
     //         to_f32(context.view.get_x(point)),
@@ -238,7 +232,7 @@ async fn draw_trails(context: &AppContext) {
         let this = (to_f32(context.view.get_x(point)), to_f32(context.view.get_y(point)));
         match last_store.get(&point.body_id) {
             Some(last) => {
-                draw_line(
+                canvas.line(
                     last.0, last.1, this.0, this.1,
                     to_f32(point.radius * 2.), point.color,
                 );
@@ -253,13 +247,24 @@ async fn draw_trails(context: &AppContext) {
     }
 }
 
-async fn draw_bodies(context: &AppContext) {
+/// Bodies glow, as rings fading out around them.
+fn draw_bodies(context: &AppContext, canvas: &mut impl Canvas) {
     for (body, _) in &context.bodies {
+        if body.test_particle {
+            canvas.rectangle(
+                to_f32(context.view.get_x(body)) - 1.0,
+                to_f32(context.view.get_y(body)) - 1.0,
+                2.0,
+                2.0,
+                body.color,
+            );
+            continue;
+        }
         let rate = context.config.shine_alpha_loss_rate;
         let mut r = to_f32(body.radius);
         let mut a = body.color.a * 0.1;
         while a > context.config.shine_alpha_min {
-            draw_circle(
+            canvas.circle(
                 to_f32(context.view.get_x(body)),
                 to_f32(context.view.get_y(body)),
                 r,
@@ -274,46 +279,59 @@ async fn draw_bodies(context: &AppContext) {
     }
 }
 
-async fn draw_points(context: &AppContext) {
+fn draw_points(context: &AppContext, canvas: &mut impl Canvas) {
     if context.ui_status.is_on(Components::Trail) {
-        draw_trails(context).await;
+        draw_trails(context, canvas);
     }
     if context.ui_status.is_on(Components::Bodies) {
-        draw_bodies(context).await;
+        draw_bodies(context, canvas);
     }
 }
 
 
-async fn draw_ui(context: &AppContext, functions: &Functions) {
+fn draw_ui(context: &AppContext, functions: &Functions, canvas: &mut impl Canvas) {
     if context.ui_status.is_on(Components::Tooltip) {
-        let tooltip_font = Some(&context.tooltip_font);
-        for (point, id) in context.bodies.iter() {
+        let style = TextStyle {
+            font: FontKind::Tooltip,
+            size: context.config.tooltip_font_size,
+            color: context.config.tooltip_font_color.into(),
+        };
+        for (point, id) in context.bodies.iter().filter(|(x, _)| !x.test_particle) {
             let body = context.controller.get_body(*id).unwrap();
             let mut txt = vec![
                 // format!(" m: {:.2}", body.mass()),
                 format!("id: {:.2}", body.id()),
                 format!(" v: {:.2}", body.speed().module()),
             ];
+            let around = match body.get_attr(&"around".into()) {
+                Some(AttrValue::USize(x)) => Some(x),
+                _ => context.controller.primary_of(*id),
+            };
+            if let Some(elements) = around.and_then(|x| context.controller.elements(*id, x)) {
+                txt.push(format!(" a: {:.2}", elements.a));
+                txt.push(format!(" e: {:.2}", elements.e));
+                txt.push(format!(" i: {:.1}°", elements.i.to_degrees()));
+            }
             for func in functions {
                 func.make_tooltip(context, &mut txt);
             }
             draw_text_lines_ex_wrap!(
+                canvas,
                 txt,
                 to_f32(context.view.get_x(point)) + 15.0,
                 to_f32(context.view.get_y(point)),
                 15.0,
-                TextParams {
-                    font: tooltip_font,
-                    font_size: context.config.tooltip_font_size,
-                    color: context.config.tooltip_font_color.into(),
-                    ..Default::default()
-                }
+                style
             );
         }
     }
     
-    let instruction_font = Some(&context.instruction_font);
     let instruction_font_size = context.config.instruction_font_size;
+    let instruction_style = TextStyle {
+        font: FontKind::Instruction,
+        size: instruction_font_size,
+        color: context.config.instruction_font_color.into(),
+    };
     if context.ui_status.is_on(Components::Help) {
         // Left Bottom
         let mut instructions: Vec<(String, String)> = vec![
@@ -324,6 +342,10 @@ async fn draw_ui(context: &AppContext, functions: &Functions) {
             ("X".into(), "Auto Zoom".into()),
             ("L".into(), format!("{} Mode", if context.steps > 0 { "Past" } else { "Present" })),
             ("S".into(), "Take Screenshot".into()),
+            ("F5".into(), "Save Snapshot".into()),
+            ("F9".into(), "Load Latest Snapshot".into()),
+            ("F".into(), "Change Reference Frame".into()),
+            ("Tab".into(), "Follow Next Body".into()),
             ("U".into(), "Toggle UI".into()),
             ("H".into(), "Toggle Help".into()),
             ("T".into(), "Toggle Tooltip".into()),
@@ -344,16 +366,12 @@ async fn draw_ui(context: &AppContext, functions: &Functions) {
             .iter()
             .map(|(k, v)| format!("[{k:^key_width$}]: {v}"));
         draw_text_lines_ex_up!(
+            canvas,
             instructions,
             10.0,
-            screen_height(),
+            canvas.height(),
             instruction_font_size as f32 + 2.0,
-            TextParams {
-                font: instruction_font,
-                font_size: instruction_font_size,
-                color: context.config.instruction_font_color.into(),
-                ..Default::default()
-            }
+            instruction_style
         );
     }
     
@@ -364,16 +382,11 @@ async fn draw_ui(context: &AppContext, functions: &Functions) {
         let length = instruction_font_size as f32 + 2.0;
         let step = 1.0;
         for Message { content, color, .. } in &context.messages {
-            draw_text_ex(
+            canvas.text(
                 content,
-                screen_width() - instruction_font_size as f32 * content.len() as f32 / 1.5,
-                screen_height() + i * length,
-                TextParams {
-                    font: instruction_font,
-                    font_size: instruction_font_size,
-                    color: *color,
-                    ..Default::default()
-                },
+                canvas.width() - instruction_font_size as f32 * content.len() as f32 / 1.5,
+                canvas.height() + i * length,
+                TextStyle { color: *color, ..instruction_style },
             );
             i += step;
         }
@@ -383,24 +396,28 @@ async fn draw_ui(context: &AppContext, functions: &Functions) {
         // Left Up
         let mut titles = vec![
             "".to_string(),
-            format!("time: {:.3}", context.time),
+            match &context.config.units {
+                Some(units) => format!("time: {}", units.format_time(context.time)),
+                None => format!("time: {:.3}", context.time),
+            },
             format!("body: {}", context.bodies.len()),
+            format!("frame: {}", context.view.frame()),
+            match context.view.target() {
+                Some(id) => format!("follow: body {}", id),
+                None => "follow: none".to_string(),
+            },
             // format!("scale: {:.2}", context.view.unit_size()),
         ];
         for func in functions {
             func.make_title(context, &mut titles);
         }
         draw_text_lines_ex_down!(
+            canvas,
             titles,
             10.,
             0.,
             instruction_font_size as f32 + 2.0,
-            TextParams {
-                font: instruction_font,
-                font_size: instruction_font_size,
-                color: context.config.instruction_font_color.into(),
-                ..Default::default()
-            }
+            instruction_style
         );
     }
 }
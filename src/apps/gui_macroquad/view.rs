@@ -1,8 +1,11 @@
 use crate::body::{BodyLike, BodyId};
+use crate::controller::Controller;
+use crate::frame::{Frame, Transform};
 use crate::num::{abs, num, Num, floor, min};
 use crate::vector::{Vector, Vector2D};
 
 use super::basic::{AppContext, Point};
+use super::config::Body;
 use super::convert::{Converter, ConverterData};
 use std::default::Default;
 
@@ -11,6 +14,12 @@ pub struct View {
     height: Num,
     converter: Converter,
     converter_data: ConverterData,
+    frame: Frame,
+    transform: Transform,
+    /// Body kept at the center of the screen.
+    target: Option<BodyId>,
+    /// Screen offset that brings the target to the center.
+    shift: Vector2D,
 }
 
 #[allow(dead_code)]
@@ -25,19 +34,24 @@ impl View {
             height,
             converter,
             converter_data,
+            frame: Frame::default(),
+            transform: Transform::default(),
+            target: None,
+            shift: Vector2D::default(),
         }
     }
     
     pub fn get_x(&self, point: &Point) -> Num {
-        point.pos.x() + self.width
+        point.pos.x() + self.shift.x() + self.width
     }
     
     pub fn get_y(&self, point: &Point) -> Num {
-        point.pos.y() + self.height
+        point.pos.y() + self.shift.y() + self.height
     }
     
+    /// Where the origin of the frame is on the screen.
     pub fn center(&self) -> Vector2D {
-        Vector2D::new(self.width, self.height)
+        Vector2D::new(self.width, self.height) + self.shift
     }
     
     pub fn unit_size(&self) -> Num {
@@ -56,6 +70,52 @@ impl View {
         self.converter.convert(v)
     }
     
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+    
+    pub fn set_frame(&mut self, frame: Frame) {
+        self.frame = frame;
+    }
+    
+    pub fn target(&self) -> Option<BodyId> {
+        self.target
+    }
+    
+    pub fn set_target(&mut self, target: Option<BodyId>) {
+        self.target = target;
+    }
+    
+    /// Move the frame along with the bodies it is attached to, and the camera along with its target.
+    pub fn follow(&mut self, controller: &Controller<Body>) {
+        self.transform = self.frame.transform(controller);
+        self.shift = match self.target.and_then(|id| controller.get_body(id)) {
+            Some(body) => Vector2D::default() - self.convert(self.to_frame(*body.pos())).0,
+            None => Vector2D::default(),
+        };
+    }
+    
+    /// Position `v` in the frame, as of the last `follow`.
+    pub fn to_frame(&self, v: Vector) -> Vector {
+        self.transform.apply(v)
+    }
+    
+    /// Offset of `v` from the center of the screen.
+    pub fn project(&self, v: Vector) -> Vector2D {
+        self.convert(self.to_frame(v)).0 + self.shift
+    }
+    
+    /// Where `v` is drawn on the screen, and how deep into it.
+    pub fn to_screen(&self, v: Vector) -> (Vector2D, Num) {
+        let (pos, depth) = self.convert(self.to_frame(v));
+        (pos + self.center(), depth)
+    }
+    
+    /// The inertial position drawn at `pos` on the screen, `depth` into it.
+    pub fn unproject(&self, pos: Vector2D, depth: Num) -> Vector {
+        self.transform.unapply(self.converter.unconvert(pos - self.center(), depth))
+    }
+    
     fn refresh_converter(&mut self) {
         // println!("New converter from data: {:?}", self.converter_data);
         self.converter = Converter::new(&self.converter_data);
@@ -78,6 +138,15 @@ impl View {
         self.height = floor(height / num(2));
     }
     
+    pub fn data(&self) -> &ConverterData {
+        &self.converter_data
+    }
+    
+    pub fn set_data(&mut self, data: ConverterData) {
+        self.converter_data = data;
+        self.refresh_converter();
+    }
+    
     pub fn reset_view(&mut self) {
         self.converter_data = Default::default();
         self.refresh_converter();
@@ -94,7 +163,7 @@ impl View {
                 .iter()
                 .map(
                     |x| {
-                        let (pos, depth) = self.converter.convert(*(x.pos()));
+                        let (pos, depth) = self.converter.convert(self.to_frame(*x.pos()));
                         (
                             Point {
                                 body_id: *x.id(),
@@ -103,6 +172,7 @@ impl View {
                                 radius: *x.mass() * context.config.radius_factor
                                     + min(depth * context.config.depth_factor, context.config.depth_max),
                                 depth,
+                                test_particle: x.is_test_particle(),
                             },
                             *x.id()
                         )
@@ -2,6 +2,7 @@ cfg_if::cfg_if! {
     if #[cfg(feature = "gui-macroquad")] {
         mod gui_macroquad;
         use gui_macroquad as app;
+        pub use app::render;
     } else
     if #[cfg(feature = "gui-nannou")] {
         mod gui_nannou;
@@ -16,4 +17,4 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use app::main;
+pub use app::{main, Body};
@@ -5,7 +5,7 @@ use crate::vector::Vector;
 pub type BodyId = usize;
 
 #[allow(dead_code)]
-pub trait BodyLike: Debug + Copy + Clone + PartialEq {
+pub trait BodyLike: Debug + Copy + Clone + PartialEq + Send + Sync {
     type Value;
     
     fn id(&self) -> &BodyId;
@@ -19,11 +19,25 @@ pub trait BodyLike: Debug + Copy + Clone + PartialEq {
     fn get_attr(&self, name: &String) -> Option<Self::Value> {
         None
     }
+    /// Test particles are moved by the others without pulling on or touching anything.
+    fn is_test_particle(&self) -> bool {
+        false
+    }
 }
 
 
 #[macro_export] macro_rules! auto_impl_body {
     ($pos: ident, $speed: ident, $mass: ident, $id: ident, $Value: tt) => {
+        $crate::auto_impl_body!($pos, $speed, $mass, $id, $Value, {});
+    };
+    ($pos: ident, $speed: ident, $mass: ident, $id: ident, $Value: tt, $test_particle: ident) => {
+        $crate::auto_impl_body!($pos, $speed, $mass, $id, $Value, {
+            fn is_test_particle(&self) -> bool {
+                self.$test_particle
+            }
+        });
+    };
+    ($pos: ident, $speed: ident, $mass: ident, $id: ident, $Value: tt, { $($extra: tt)* }) => {
 impl BodyLike for Body {
     type Value = $Value;
     
@@ -48,6 +62,7 @@ impl BodyLike for Body {
     fn mass_mut(&mut self) -> &mut Num {
         &mut self.$mass
     }
+    $($extra)*
 }
     };
 }
@@ -1,13 +1,24 @@
 use std::fs::File;
 use std::io::Read;
 use anyhow::Context;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
 pub use anyhow::Result as EResult;
+use std::collections::HashMap;
+use crate::body::BodyId;
+use crate::num::{num, Num};
+use crate::orbit::Elements;
+use crate::units::Units;
+use crate::vector::Vector;
 #[cfg(feature = "clap")]
-use clap::Parser;
+use crate::presets;
+#[cfg(feature = "clap")]
+use crate::simulate;
+#[cfg(feature = "clap")]
+use clap::{Parser, Subcommand};
 
 #[cfg(feature = "clap")]
-fn default_config_file() -> String {
+pub fn default_config_file() -> String {
     // "examples/three_1.toml".into()
     "examples/round_4.toml".into()
 }
@@ -22,34 +33,223 @@ pub fn load_config<T>(filename: &str) -> EResult<T>
     file.read_to_string(&mut content)
         .with_context(|| format!("Failed to read config file: {}", filename))
         ?;
-    let config: T = toml::from_str(content.as_str())
+    let config: T = parse_config(content.as_str())
         .with_context(|| format!("Failed to parse config file: {}", filename))
         ?;
     Ok(config)
 }
 
+/// Parse a config, converting the values with a unit suffix into its `[units]` first.
+/// Its gravitational constant is set as configured, else to G in its units, else to 1 for dimensionless configs.
+pub fn parse_config<T>(content: &str) -> EResult<T>
+    where for<'de> T: Deserialize<'de> {
+    parse_table(toml::from_str(content)?)
+}
+
+/// Deserialize a config kept inside another file, like a replay, as `parse_config` does.
+pub fn deserialize_config<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where D: Deserializer<'de>, for<'a> T: Deserialize<'a> {
+    let table = toml::Table::deserialize(deserializer)?;
+    parse_table(table).map_err(|x| D::Error::custom(format!("{:#}", x)))
+}
+
+fn parse_table<T>(mut table: toml::Table) -> EResult<T>
+    where for<'de> T: Deserialize<'de> {
+    let units = match table.get("units") {
+        Some(units) => Some(Units::deserialize(units.clone()).context("Invalid [units] table")?),
+        None => None,
+    };
+    crate::units::convert_bodies(&mut table, units.as_ref()).map_err(anyhow::Error::msg)?;
+    let gravitational_constant = match table.get("gravitational_constant") {
+        Some(x) => number(x).context("Invalid gravitational_constant")?,
+        None => units.as_ref().map_or(num(1), Units::gravitational_constant),
+    };
+    table.insert("gravitational_constant".into(), toml::Value::Float(gravitational_constant));
+    place_orbits(&mut table, gravitational_constant)?;
+    Ok(T::deserialize(table)?)
+}
+
+fn number(x: &toml::Value) -> Option<Num> {
+    x.as_float().or_else(|| x.as_integer().map(|x| x as Num))
+}
+
+/// Where a body is placed by its orbital elements, relative to another body of the config.
+#[derive(Deserialize)]
+struct Orbit {
+    around: BodyId,
+    a: Num,
+    #[serde(default)]
+    e: Num,
+    #[serde(default)]
+    i: Num,
+    #[serde(default)]
+    omega: Num,
+    #[serde(default, rename = "Omega")]
+    node: Num,
+    #[serde(default)]
+    nu: Num,
+}
+
+impl Orbit {
+    fn elements(&self) -> Elements {
+        Elements { a: self.a, e: self.e, i: self.i, omega: self.omega, node: self.node, nu: self.nu }
+    }
+}
+
+/// Give the bodies placed by an `orbit` their `pos` and `speed`, around a body defined before them
+/// in the same config, with `gravitational_constant` being the one of the config.
+fn place_orbits(table: &mut toml::Table, gravitational_constant: Num) -> EResult<()> {
+    let Some(bodies) = table.get_mut("bodies").and_then(toml::Value::as_array_mut) else {
+        return Ok(());
+    };
+    let vector = |x: Option<&toml::Value>| -> EResult<Vector> {
+        Ok(x.map(|x| Vector::deserialize(x.clone())).transpose()?.unwrap_or_default())
+    };
+    
+    // Position, speed and mass of the bodies with an id so far.
+    let mut placed: HashMap<BodyId, (Vector, Vector, Num)> = HashMap::new();
+    for (index, body) in bodies.iter_mut().enumerate() {
+        let Some(body) = body.as_table_mut() else {
+            continue;
+        };
+        let id = body.get("id").and_then(toml::Value::as_integer);
+        let name = id.map_or_else(|| format!("#{}", index), |x| x.to_string());
+        let test_particle = body.get("test_particle").and_then(toml::Value::as_bool).unwrap_or(false);
+        let mass = match body.get("mass") {
+            Some(x) => number(x).with_context(|| format!("Invalid mass of body {}", name))?,
+            None if test_particle => num(0),
+            None => num(1),
+        };
+        
+        if let Some(orbit) = body.get("orbit") {
+            anyhow::ensure!(
+                !body.contains_key("pos") && !body.contains_key("speed"),
+                "Body {} has both an orbit and pos/speed", name,
+            );
+            let orbit = Orbit::deserialize(orbit.clone())
+                .with_context(|| format!("Invalid orbit of body {}", name))?;
+            let &(parent_pos, parent_speed, parent_mass) = placed.get(&orbit.around)
+                .with_context(|| format!("Body {} orbits body {}, which is not defined before it", name, orbit.around))?;
+            let elements = orbit.elements();
+            elements.check()
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid orbit of body {}", name))?;
+            let (pos, speed) = elements.to_state(gravitational_constant * (parent_mass + mass));
+            let array = |x: Vector| toml::Value::Array(
+                [x.x(), x.y(), x.z()].into_iter().map(toml::Value::Float).collect()
+            );
+            body.insert("pos".into(), array(parent_pos + pos));
+            body.insert("speed".into(), array(parent_speed + speed));
+        }
+        
+        if let Some(id) = id {
+            let pos = vector(body.get("pos")).with_context(|| format!("Invalid pos of body {}", name))?;
+            let speed = vector(body.get("speed")).with_context(|| format!("Invalid speed of body {}", name))?;
+            placed.insert(id as BodyId, (pos, speed, mass));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(not(feature = "clap"))]
 pub fn load_config<T>() -> EResult<T>
     where for<'de> T: Deserialize<'de> {
-    let config: T = toml::from_str(include_str!("../examples/round_4.toml"))
+    let config: T = parse_config(include_str!("../examples/round_4.toml"))
         ?;
     Ok(config)
 }
 
 #[cfg(feature = "clap")]
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(default_value_t = default_config_file())]
     file_name: String,
+    /// Run a built-in scenario instead of the config file
+    #[arg(long, conflicts_with = "file_name")]
+    preset: Option<String>,
+    /// List the built-in scenarios and exit
+    #[arg(long)]
+    list_presets: bool,
+    /// Record the session into a replay file, written on exit
+    #[arg(long, value_name = "FILE")]
+    record: Option<String>,
+    /// Play back a replay file instead of the config file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["file_name", "preset", "record"])]
+    replay: Option<String>,
+}
+
+#[cfg(feature = "clap")]
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a config without a window and write the sampled states
+    Simulate(simulate::Args),
+    /// Render a config into a sequence of PNG frames, without a window nor a GPU
+    #[cfg(feature = "gui-macroquad")]
+    Render(crate::apps::render::Args),
+}
+
+#[cfg(feature = "clap")]
+lazy_static! {
+    static ref ARGS: Args = Args::parse();
+}
+
+/// The subcommand to run instead of the frontend, if any.
+#[cfg(feature = "clap")]
+pub fn command() -> Option<&'static Command> {
+    ARGS.command.as_ref()
+}
+
+/// The replay file to record the session into, if any.
+#[cfg(feature = "clap")]
+pub fn record_file() -> Option<&'static str> {
+    ARGS.record.as_deref()
+}
+
+/// The replay file to play back, if any.
+#[cfg(feature = "clap")]
+pub fn replay_file() -> Option<&'static str> {
+    ARGS.replay.as_deref()
+}
+
+#[cfg(not(feature = "clap"))]
+pub fn record_file() -> Option<&'static str> {
+    None
+}
+
+#[cfg(not(feature = "clap"))]
+pub fn replay_file() -> Option<&'static str> {
+    None
+}
+
+/// Load a built-in preset by name, or else the config file.
+#[cfg(feature = "clap")]
+pub fn load<T>(file_name: &str, preset: Option<&str>) -> EResult<T>
+    where for<'de> T: Deserialize<'de>
+{
+    if let Some(name) = preset {
+        let preset = presets::find(name)
+            .with_context(|| format!("Unknown preset: {}, see --list-presets", name))?;
+        return parse_config::<T>(preset.config)
+            .with_context(|| format!("Failed to parse preset: {}", name));
+    }
+    load_config::<T>(file_name)
 }
 
 #[cfg(feature = "clap")]
 pub fn init<T>() -> EResult<T>
     where for<'de> T: Deserialize<'de>
 {
-    let args = Args::parse();
-    Ok(load_config::<T>(&args.file_name)?)
+    let args = &*ARGS;
+    if args.list_presets {
+        for preset in presets::PRESETS {
+            println!("{:<16}{}", preset.name, preset.description);
+        }
+        std::process::exit(0);
+    }
+    load::<T>(&args.file_name, args.preset.as_deref())
 }
 
 #[cfg(not(feature = "clap"))]
@@ -58,3 +258,81 @@ pub fn init<T>() -> EResult<T>
 {
     Ok(load_config::<T>()?)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+    struct Body {
+        #[serde(default)]
+        pos: Vector,
+        #[serde(default)]
+        speed: Vector,
+        mass: Num,
+    }
+    
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        bodies: Vec<Body>,
+        gravitational_constant: Num,
+    }
+    
+    #[test]
+    fn test_config_units() {
+        let config: Config = parse_config(r#"
+            [units]
+            length = "AU"
+            mass = "Msun"
+            time = "day"
+            
+            [[bodies]]
+            pos = ["1 AU", "149597870.7 km", 0]
+            mass = "2 Msun"
+        "#).unwrap();
+        let body = config.bodies[0];
+        assert!(body.pos.distance(Vector::new(1, 1, 0)) < 1e-12, "{}", body.pos);
+        assert_eq!(body.mass, num(2));
+        assert!((config.gravitational_constant - 0.01720209895 * 0.01720209895).abs() < 1e-7);
+        
+        // Without units, the same text is dimensionless.
+        let config: Config = parse_config("[[bodies]]\nmass = 2").unwrap();
+        assert_eq!(config.gravitational_constant, num(1));
+        assert!(parse_config::<Config>("[[bodies]]\nmass = \"2 Msun\"").is_err());
+    }
+    
+    #[test]
+    fn test_config_orbit() {
+        let config: Config = parse_config(r#"
+            gravitational_constant = 4
+            
+            [[bodies]]
+            id = 7
+            pos = [1, 0, 0]
+            speed = [0, 1, 0]
+            mass = 3
+            
+            [[bodies]]
+            mass = 1
+            orbit = { around = 7, a = 2 }
+        "#).unwrap();
+        // Circular at a speed of sqrt(G * (3 + 1) / 2) around a body of the same config.
+        let body = config.bodies[1];
+        assert!(body.pos.distance(Vector::new(3, 0, 0)) < 1e-12, "{}", body.pos);
+        assert!(body.speed.distance(Vector::new(0, num(1) + num(8).sqrt(), 0)) < 1e-12, "{}", body.speed);
+    }
+    
+    #[test]
+    fn test_config_orbit_errors() {
+        let parse = |bodies: &str| parse_config::<Config>(&format!(
+            "[[bodies]]\nid = 0\nmass = 1\n\n[[bodies]]\nmass = 0.1\n{}", bodies,
+        ));
+        assert!(parse("orbit = { around = 0, a = 1, e = 0.5 }").is_ok());
+        // Body 3 of another config is no body of this one.
+        parse_config::<Config>("[[bodies]]\nid = 3\nmass = 1").unwrap();
+        assert!(parse("orbit = { around = 3, a = 1 }").is_err());
+        assert!(parse("orbit = { around = 0, a = 1 }\npos = [1, 0, 0]").is_err());
+        assert!(parse("orbit = { around = 0, a = 1, e = 1.5 }").is_err());
+        assert!(parse("orbit = { around = 0, a = -1, e = 1.5 }").is_ok());
+    }
+}
@@ -1,10 +1,18 @@
 use std::slice::{Iter, IterMut};
 use crate::body::{BodyLike, BodyId};
-use crate::num::{num, square, Num};
+use crate::gravity::Gravity;
+use crate::history::Keyframe;
+use crate::integrator::Integrator;
+use crate::num::{abs, num, Num};
+use crate::orbit::Elements;
+use crate::vector::Vector;
 
 pub struct Controller<B: BodyLike> {
     bodies: Vec<B>,
     time: Num,
+    integrator: Box<dyn Integrator<B>>,
+    gravity: Gravity,
+    initial: Conserved,
 }
 
 pub enum IterStatus<T> {
@@ -14,34 +22,106 @@ pub enum IterStatus<T> {
 
 #[allow(dead_code)]
 impl<B: BodyLike> Controller<B> {
-    pub fn new(bodies: Vec<B>) -> Self {
-        Self { bodies, time: num(0) }
+    pub fn new(bodies: Vec<B>, integrator: Box<dyn Integrator<B>>, gravity: Gravity) -> Self {
+        let mut controller = Self {
+            bodies,
+            time: num(0),
+            integrator,
+            gravity,
+            initial: Conserved { energy: num(0), momentum: Vector::origin(), angular_momentum: Vector::origin() },
+        };
+        controller.initial = controller.conserved();
+        controller
     }
     
     pub fn update(&mut self, delta: Num) {
-        for i in 0..self.bodies.len() {
-            for j in (i + 1)..self.bodies.len() {
-                let a = self.bodies[i];
-                let b = self.bodies[j];
-                let tmp = *a.pos() - *b.pos();
-                let module = tmp.module();
-                let fac = square(module);
-                let tmp = tmp / module;
-                *self.bodies[i].speed_mut() += tmp * (-delta * b.mass() / fac);
-                *self.bodies[j].speed_mut() += tmp * (delta * a.mass() / fac);
+        let gravity = &self.gravity;
+        self.integrator.step(&mut self.bodies, delta, &|bodies, acc| gravity.accelerations(bodies, acc));
+        self.time += delta;
+    }
+    
+    /// Like `update`, unless two bodies not touching before come within `contact` of each other on the way.
+    /// Then stop at the time they first touch, found by bisection to within `precision`, and return them.
+    pub fn update_until_contact<F: Fn(&B, &B) -> Num>(&mut self, delta: Num, contact: F, precision: Num) -> Option<(BodyId, BodyId)> {
+        if !self.may_touch(delta, &contact) {
+            self.update(delta);
+            return None;
+        }
+        
+        let start = self.bodies.clone();
+        let time = self.time;
+        // Each try starts over with the step size the adaptive integrators had.
+        let carried = self.integrator.carried_step();
+        self.update(delta);
+        let mut found = first_contact(&start, &self.bodies, &contact)?;
+        
+        let (mut lo, mut hi) = (num(0), delta);
+        while abs(hi - lo) > precision {
+            let mid = (lo + hi) / num(2);
+            self.bodies.copy_from_slice(&start);
+            self.time = time;
+            self.integrator.set_carried_step(carried);
+            self.update(mid);
+            match first_contact(&start, &self.bodies, &contact) {
+                Some(pair) => {
+                    hi = mid;
+                    found = pair;
+                }
+                None => lo = mid,
             }
         }
-        for a in self.bodies.iter_mut() {
-            let pos = *a.speed() * delta;
-            *a.pos_mut() += pos;
+        self.bodies.copy_from_slice(&start);
+        self.time = time;
+        self.integrator.set_carried_step(carried);
+        self.update(hi);
+        Some(found)
+    }
+    
+    /// Whether two massive bodies may come within `contact` of each other in a step of `delta`.
+    /// Each pair is bounded by its relative speed, plus twice what it would cover under the pull of the field now,
+    /// everything included, and of each other once touching, for the pull to grow on the way.
+    fn may_touch<F: Fn(&B, &B) -> Num>(&self, delta: Num, contact: &F) -> bool {
+        let delta = abs(delta);
+        let mut acc = Vec::with_capacity(self.bodies.len());
+        self.gravity.accelerations(&self.bodies, &mut acc);
+        for (i, a) in self.bodies.iter().enumerate().filter(|(_, x)| !x.is_test_particle()) {
+            for (j, b) in self.bodies.iter().enumerate().skip(i + 1).filter(|(_, x)| !x.is_test_particle()) {
+                let distance = contact(a, b);
+                let pull = self.gravity.constant * (*a.mass() + *b.mass()) / (distance * distance)
+                    + acc[i].module() + acc[j].module();
+                let reach = a.speed().distance(*b.speed()) * delta + pull * delta * delta;
+                if a.pos().distance(*b.pos()) - distance <= reach {
+                    return true;
+                }
+            }
         }
-        self.time += delta;
+        false
+    }
+    
+    pub fn gravity(&self) -> &Gravity {
+        &self.gravity
     }
     
     pub fn total_seconds(&self) -> Num {
         self.time
     }
     
+    /// Continue from `time`, e.g. when resuming a snapshot.
+    pub fn set_time(&mut self, time: Num) {
+        self.time = time;
+    }
+    
+    pub fn keyframe(&self) -> Keyframe<B> {
+        Keyframe { time: self.time, bodies: self.bodies.clone(), carried_step: self.integrator.carried_step() }
+    }
+    
+    /// Go back to the state of `keyframe`, taken earlier in the same run.
+    pub fn restore(&mut self, keyframe: &Keyframe<B>) {
+        self.bodies.clone_from(&keyframe.bodies);
+        self.time = keyframe.time;
+        self.integrator.set_carried_step(keyframe.carried_step);
+    }
+    
     pub fn len(&self) -> usize {
         self.bodies.len()
     }
@@ -50,20 +130,30 @@ impl<B: BodyLike> Controller<B> {
         self.bodies.iter()
     }
     
+    /// Call `f` with each pair of massive bodies until it is done, test particles are left out.
     pub fn with_each_other<T, F: FnMut(&B, &B) -> IterStatus<T>>(&self, mut f: F) -> Option<T> {
-        for i in 0..self.bodies.len() {
-            for j in (i + 1)..self.bodies.len() {
-                let a = &self.bodies[i];
-                let b = &self.bodies[j];
-                if let IterStatus::Done(x) = f(a, b) {
-                    return Some(x);
-                }
+        for (i, j) in massive_pairs(&self.bodies) {
+            let a = &self.bodies[i];
+            let b = &self.bodies[j];
+            if let IterStatus::Done(x) = f(a, b) {
+                return Some(x);
             }
         }
         
         None
     }
     
+    /// Like `with_each_other`, but goes through every pair and collects what `f` is done with.
+    pub fn collect_each_other<T, F: FnMut(&B, &B) -> IterStatus<T>>(&self, mut f: F) -> Vec<T> {
+        let mut found = Vec::new();
+        for (i, j) in massive_pairs(&self.bodies) {
+            if let IterStatus::Done(x) = f(&self.bodies[i], &self.bodies[j]) {
+                found.push(x);
+            }
+        }
+        found
+    }
+    
     pub fn iter_mut(&mut self) -> IterMut<B> {
         self.bodies.iter_mut()
     }
@@ -76,13 +166,268 @@ impl<B: BodyLike> Controller<B> {
         }
         None
     }
+    
+    pub fn get_body_mut(&mut self, id: BodyId) -> Option<&mut B> {
+        self.bodies.iter_mut().find(|x| *x.id() == id)
+    }
+    
+    pub fn add(&mut self, body: B) {
+        self.bodies.push(body);
+    }
+    
+    pub fn remove(&mut self, id: BodyId) -> Option<B> {
+        let index = self.bodies.iter().position(|x| *x.id() == id)?;
+        Some(self.bodies.remove(index))
+    }
+    
+    /// Returns whether there is such a body to move.
+    pub fn set_pos(&mut self, id: BodyId, pos: Vector) -> bool {
+        self.get_body_mut(id).map(|x| *x.pos_mut() = pos).is_some()
+    }
+    
+    /// Returns whether there is such a body to speed up.
+    pub fn set_speed(&mut self, id: BodyId, speed: Vector) -> bool {
+        self.get_body_mut(id).map(|x| *x.speed_mut() = speed).is_some()
+    }
+    
+    /// An id that no body has.
+    pub fn next_id(&self) -> BodyId {
+        self.bodies.iter().map(|x| *x.id() + 1).max().unwrap_or(0)
+    }
+    
+    /// Measure the drift from now on, after the bodies were edited by hand.
+    pub fn rebase(&mut self) {
+        self.initial = self.conserved();
+    }
+    
+    /// Merge `other` into `id` at their center of mass, conserving mass and momentum.
+    /// Returns the body that was absorbed.
+    pub fn merge(&mut self, id: BodyId, other: BodyId) -> Option<B> {
+        if id == other || self.get_body(id).is_none() {
+            return None;
+        }
+        let absorbed = self.remove(other)?;
+        let body = self.get_body_mut(id).unwrap();
+        let (m1, m2) = (*body.mass(), *absorbed.mass());
+        let mass = m1 + m2;
+        if mass != num(0) {
+            *body.pos_mut() = (*body.pos() * m1 + *absorbed.pos() * m2) / mass;
+            *body.speed_mut() = (*body.speed() * m1 + *absorbed.speed() * m2) / mass;
+        }
+        *body.mass_mut() = mass;
+        Some(absorbed)
+    }
+    
+    /// Resolve the contact of `id` and `other` as hard spheres whose centers touch at `contact` distance.
+    /// Approaching bodies get opposite impulses along the line of centers, keeping the share
+    /// `restitution` of their normal relative speed, and overlapping ones are pushed apart
+    /// around their center of mass. Returns whether an impulse was applied.
+    pub fn bounce(&mut self, id: BodyId, other: BodyId, restitution: Num, contact: Num) -> bool {
+        let (Some(i), Some(j)) = (
+            self.bodies.iter().position(|x| *x.id() == id),
+            self.bodies.iter().position(|x| *x.id() == other),
+        ) else {
+            return false;
+        };
+        let (a, b) = (self.bodies[i], self.bodies[j]);
+        let diff = *b.pos() - *a.pos();
+        let (ma, mb) = (*a.mass(), *b.mass());
+        if i == j || diff.is_zero() || ma + mb == num(0) {
+            return false;
+        }
+        
+        let normal = diff.unit();
+        let overlap = contact - diff.module();
+        if overlap > num(0) {
+            *self.bodies[i].pos_mut() -= normal * (overlap * mb / (ma + mb));
+            *self.bodies[j].pos_mut() += normal * (overlap * ma / (ma + mb));
+        }
+        
+        let approach = (*b.speed() - *a.speed()).dot_prod(&normal);
+        if approach >= num(0) {
+            return false;
+        }
+        // Impulse on `b`, the change of momentum of each body along the normal.
+        let impulse = normal * (-(num(1) + restitution) * approach * ma * mb / (ma + mb));
+        *self.bodies[i].speed_mut() -= impulse / ma;
+        *self.bodies[j].speed_mut() += impulse / mb;
+        true
+    }
+    
+    /// The body pulling hardest on `id`, which its osculating orbit is best taken around.
+    pub fn primary_of(&self, id: BodyId) -> Option<BodyId> {
+        let this = self.get_body(id)?;
+        self.bodies.iter()
+            .filter(|x| *x.id() != id)
+            .map(|x| (*x.id(), *x.mass() / x.pos().distance(*this.pos()).powi(2)))
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+            .map(|x| x.0)
+    }
+    
+    /// Osculating orbital elements of `id` relative to `around`.
+    pub fn elements(&self, id: BodyId, around: BodyId) -> Option<Elements> {
+        let this = self.get_body(id)?;
+        let other = self.get_body(around)?;
+        let mu = self.gravity.constant * (this.mass() + other.mass());
+        Some(Elements::from_state(*this.pos() - *other.pos(), *this.speed() - *other.speed(), mu))
+    }
+    
+    /// Gravitational acceleration of `id` from the massive bodies.
+    pub fn acceleration(&self, id: BodyId) -> Option<Vector> {
+        let this = self.get_body(id)?;
+        Some(self.bodies.iter()
+            .filter(|x| *x.id() != id && !x.is_test_particle())
+            .fold(Vector::origin(), |acc, x| acc + self.gravity.pull(*this.pos() - *x.pos(), *x.mass())))
+    }
+    
+    pub fn total_mass(&self) -> Num {
+        self.bodies.iter().map(|x| *x.mass()).sum()
+    }
+    
+    pub fn kinetic_energy(&self) -> Num {
+        self.bodies.iter()
+            .map(|x| *x.mass() * x.speed().dot_prod(x.speed()) / num(2))
+            .sum()
+    }
+    
+    pub fn potential_energy(&self) -> Num {
+        let mut energy = num(0);
+        for i in 0..self.bodies.len() {
+            for j in (i + 1)..self.bodies.len() {
+                let a = &self.bodies[i];
+                let b = &self.bodies[j];
+                energy += self.gravity.potential(a, b);
+            }
+        }
+        energy
+    }
+    
+    pub fn total_energy(&self) -> Num {
+        self.kinetic_energy() + self.potential_energy()
+    }
+    
+    pub fn momentum(&self) -> Vector {
+        self.bodies.iter()
+            .fold(Vector::origin(), |acc, x| acc + *x.speed() * *x.mass())
+    }
+    
+    pub fn angular_momentum(&self) -> Vector {
+        self.bodies.iter()
+            .fold(Vector::origin(), |acc, x| acc + x.pos().cross_prod(&(*x.speed() * *x.mass())))
+    }
+    
+    /// The plain average of the positions, as long as no body has any mass.
+    pub fn center_of_mass(&self) -> Vector {
+        let mass = self.total_mass();
+        if mass == num(0) {
+            return self.average(|x| *x.pos());
+        }
+        self.bodies.iter()
+            .fold(Vector::origin(), |acc, x| acc + *x.pos() * *x.mass())
+            / mass
+    }
+    
+    /// The plain average of the speeds, as long as no body has any mass.
+    pub fn center_of_mass_speed(&self) -> Vector {
+        let mass = self.total_mass();
+        if mass == num(0) {
+            return self.average(|x| *x.speed());
+        }
+        self.momentum() / mass
+    }
+    
+    fn average(&self, value: impl Fn(&B) -> Vector) -> Vector {
+        if self.bodies.is_empty() {
+            return Vector::origin();
+        }
+        self.bodies.iter().fold(Vector::origin(), |acc, x| acc + value(x)) / num(self.bodies.len() as f64)
+    }
+    
+    pub fn conserved(&self) -> Conserved {
+        Conserved {
+            energy: self.total_energy(),
+            momentum: self.momentum(),
+            angular_momentum: self.angular_momentum(),
+        }
+    }
+    
+    /// Relative drift of the conserved quantities since the controller was created, or last rebased.
+    pub fn drift(&self) -> Drift {
+        self.conserved().drift_from(&self.initial)
+    }
+}
+
+/// Whether relative positions moving in a straight line from `from` to `to` come within `distance`.
+fn swept_within(from: Vector, to: Vector, distance: Num) -> bool {
+    let path = to - from;
+    let length = path.dot_prod(&path);
+    let s = if length == num(0) { num(0) } else { (-from.dot_prod(&path) / length).clamp(num(0), num(1)) };
+    (from + path * s).module() <= distance
+}
+
+/// Index pairs of the massive bodies, in order, as test particles never touch anything.
+fn massive_pairs<B: BodyLike>(bodies: &[B]) -> Vec<(usize, usize)> {
+    let massive: Vec<usize> = (0..bodies.len()).filter(|&i| !bodies[i].is_test_particle()).collect();
+    let mut pairs = Vec::new();
+    for (k, &i) in massive.iter().enumerate() {
+        pairs.extend(massive[k + 1..].iter().map(|&j| (i, j)));
+    }
+    pairs
+}
+
+/// The first pair of bodies, apart in `before`, to touch on the way to `after`, even if only in passing.
+fn first_contact<B: BodyLike, F: Fn(&B, &B) -> Num>(before: &[B], after: &[B], contact: &F) -> Option<(BodyId, BodyId)> {
+    for (i, j) in massive_pairs(after) {
+        let distance = contact(&after[i], &after[j]);
+        let from = *before[j].pos() - *before[i].pos();
+        if from.module() > distance && swept_within(from, *after[j].pos() - *after[i].pos(), distance) {
+            return Some((*after[i].id(), *after[j].id()));
+        }
+    }
+    None
+}
+
+/// Quantities that an isolated system keeps constant.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Conserved {
+    pub energy: Num,
+    pub momentum: Vector,
+    pub angular_momentum: Vector,
+}
+
+/// Size of the change of each conserved quantity, relative to its initial size where that is not zero.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Drift {
+    pub energy: Num,
+    pub momentum: Num,
+    pub angular_momentum: Num,
+}
+
+fn relative(change: Num, scale: Num) -> Num {
+    if scale == num(0) { change } else { change / scale }
+}
+
+impl Conserved {
+    pub fn drift_from(&self, initial: &Conserved) -> Drift {
+        Drift {
+            energy: relative(abs(self.energy - initial.energy), abs(initial.energy)),
+            momentum: relative(
+                (self.momentum - initial.momentum).module(),
+                initial.momentum.module(),
+            ),
+            angular_momentum: relative(
+                (self.angular_momentum - initial.angular_momentum).module(),
+                initial.angular_momentum.module(),
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::fmt::Debug;
     use crate::auto_impl_body;
-    use crate::vector::Vector;
+    use crate::integrator::{IntegratorKind, StepControl};
     use crate::num::{sqrt, Int};
     use super::*;
     
@@ -146,24 +491,46 @@ mod test {
         test_con_round(bodies, num(2));
     }
     
+    /// Steps each integrator is run for, fewer for those evaluating more per step,
+    /// and how far off its circle a body may end up.
+    fn round_limits(kind: IntegratorKind) -> (Int, Num) {
+        match kind {
+            IntegratorKind::Euler => (COUNT, num(DIFF)),
+            IntegratorKind::Leapfrog => (COUNT / 10, num(1e-9)),
+            IntegratorKind::Yoshida => (COUNT / 30, num(1e-9)),
+            IntegratorKind::Rk4 => (COUNT / 40, num(1e-9)),
+            IntegratorKind::Dopri45 => (COUNT / 100, num(1e-9)),
+        }
+    }
+    
+    /// Every integrator is run, so that one leaving the round does not hide how the others do.
     fn test_con_round(bodies: Vec<Body>, r: Num) {
-        let mut con = Controller::new(bodies);
+        let failed: Vec<IntegratorKind> = IntegratorKind::ALL.into_iter()
+            .filter(|&kind| !test_con_round_with(bodies.clone(), r, kind))
+            .collect();
+        assert!(failed.is_empty(), "Not in round! ({:?})", failed);
+    }
+    
+    fn test_con_round_with(bodies: Vec<Body>, r: Num, kind: IntegratorKind) -> bool {
+        let mut con = Controller::new(bodies, kind.build(StepControl::default()), Gravity::default());
         let t = num(DELTA);
-        for _ in 0..COUNT {
+        let (count, diff) = round_limits(kind);
+        for _ in 0..count {
             con.update(t);
             for a in &con.bodies {
-                if a.pos.distance(Vector::origin()) - r >= num(DIFF) {
+                if a.pos.distance(Vector::origin()) - r >= diff {
                     println!(
                         "{}, {}, {}",
                         a.pos,
                         a.speed,
                         a.pos.distance(Vector::origin())
                     );
-                    println!("Expect: {}+-{}", r, DIFF);
-                    panic!("Not in round!");
+                    println!("Expect: {}+-{} ({:?})", r, diff, kind);
+                    return false;
                 }
             }
         }
+        true
     }
     
     #[test]
@@ -200,6 +567,12 @@ mod test {
     
     #[test]
     fn test_con_double() {
+        for kind in IntegratorKind::ALL {
+            test_con_double_with(kind);
+        }
+    }
+    
+    fn test_con_double_with(kind: IntegratorKind) {
         let bodies = vec![
             Body {
                 pos: Vector::new(-1, 0, 0),
@@ -214,12 +587,347 @@ mod test {
                 id: 1,
             },
         ];
-        let mut con = Controller::new(bodies);
+        let mut con = Controller::new(bodies, kind.build(StepControl::default()), Gravity::default());
         let t = num(DELTA);
-        for _ in 0..COUNT {
+        let (count, _) = round_limits(kind);
+        for _ in 0..count {
             con.update(t);
             assert!(con.bodies[0].pos.distance(Vector::origin()) - num(1) < num(DIFF));
             assert!(con.bodies[1].pos.distance(Vector::origin()) - num(3) < num(DIFF));
         }
     }
+    
+    #[test]
+    fn test_con_adaptive() {
+        let mass = num(4);
+        let bodies = vec![
+            Body {
+                pos: Vector::new(0, 1, 0),
+                speed: Vector::new(-1, 0, 0),
+                mass,
+                id: 0,
+            },
+            Body {
+                pos: Vector::new(0, -1, 0),
+                speed: Vector::new(1, 0, 0),
+                mass,
+                id: 1,
+            },
+        ];
+        let mut con = Controller::new(
+            bodies,
+            IntegratorKind::Dopri45.build(StepControl::default()),
+            Gravity::default(),
+        );
+        let t = num(0.37);
+        let mut time = num(0);
+        for _ in 0..100 {
+            con.update(t);
+            time += t;
+            assert_eq!(con.total_seconds(), time);
+            for a in &con.bodies {
+                assert!((a.pos.distance(Vector::origin()) - num(1)).abs() < num(DIFF));
+            }
+        }
+    }
+    
+    #[test]
+    fn test_con_conserved() {
+        let mass = num(4);
+        let bodies = vec![
+            Body {
+                pos: Vector::new(0, 1, 0),
+                speed: Vector::new(-1, 0, 0),
+                mass,
+                id: 0,
+            },
+            Body {
+                pos: Vector::new(0, -1, 0),
+                speed: Vector::new(1, 0, 0),
+                mass,
+                id: 1,
+            },
+        ];
+        let mut con = Controller::new(
+            bodies,
+            IntegratorKind::Yoshida.build(StepControl::default()),
+            Gravity::default(),
+        );
+        assert_eq!(con.kinetic_energy(), num(4));
+        assert_eq!(con.potential_energy(), num(-8));
+        assert_eq!(con.momentum(), Vector::origin());
+        assert_eq!(con.angular_momentum(), Vector::new(0, 0, 8));
+        assert_eq!(con.center_of_mass(), Vector::origin());
+        assert_eq!(con.drift(), Drift::default());
+        
+        for _ in 0..10000 {
+            con.update(num(0.001));
+        }
+        let drift = con.drift();
+        assert!(drift.energy < num(1e-9));
+        assert!(drift.momentum < num(1e-9));
+        assert!(drift.angular_momentum < num(1e-9));
+        assert!(con.center_of_mass_speed().module() < num(1e-9));
+    }
+    
+    #[test]
+    fn test_con_edit() {
+        let bodies = vec![
+            Body { pos: Vector::new(-1, 0, 0), speed: Vector::new(0, -0.5, 0), mass: num(1), id: 0 },
+            Body { pos: Vector::new(1, 0, 0), speed: Vector::new(0, 0.5, 0), mass: num(1), id: 3 },
+        ];
+        let mut con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        assert_eq!(con.next_id(), 4);
+        assert!(con.set_pos(3, Vector::new(2, 0, 0)));
+        assert!(con.set_speed(3, Vector::new(0, 1, 0)));
+        assert!(!con.set_pos(1, Vector::origin()));
+        assert_eq!(con.get_body(3).unwrap().pos, Vector::new(2, 0, 0));
+        assert_eq!(con.get_body(3).unwrap().speed, Vector::new(0, 1, 0));
+        
+        // The edits are no drift, once rebased.
+        assert!(con.drift().energy > num(0.1));
+        con.rebase();
+        assert_eq!(con.drift().energy, num(0));
+        con.remove(3);
+        con.remove(0);
+        assert_eq!(con.next_id(), 0);
+    }
+    
+    #[test]
+    fn test_con_acceleration() {
+        let bodies: Vec<Body> = (0..4).map(|id| Body {
+            pos: Vector::new(id as f64, (id * id) as f64, 0),
+            speed: Vector::origin(),
+            mass: num(id as f64),
+            id,
+        }).collect();
+        let gravity = Gravity { constant: num(3), softening: num(0.1), ..Default::default() };
+        let mut all = Vec::new();
+        gravity.accelerations(&bodies, &mut all);
+        let con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), gravity);
+        for (id, acc) in all.iter().enumerate() {
+            assert!(con.acceleration(id).unwrap().distance(*acc) < 1e-12);
+        }
+        assert_eq!(con.acceleration(4), None);
+    }
+    
+    #[test]
+    fn test_con_gravity_constant() {
+        // Doubling G while halving the masses keeps the orbit of test_con_2.
+        let mass = num(2);
+        let bodies = vec![
+            Body {
+                pos: Vector::new(0, 1, 0),
+                speed: Vector::new(-1, 0, 0),
+                mass,
+                id: 0,
+            },
+            Body {
+                pos: Vector::new(0, -1, 0),
+                speed: Vector::new(1, 0, 0),
+                mass,
+                id: 1,
+            },
+        ];
+        let gravity = Gravity { constant: num(2), ..Default::default() };
+        let mut con = Controller::new(bodies, IntegratorKind::Yoshida.build(StepControl::default()), gravity);
+        assert_eq!(con.potential_energy(), num(-4));
+        for _ in 0..10000 {
+            con.update(num(0.001));
+            for a in &con.bodies {
+                assert!((a.pos.distance(Vector::origin()) - num(1)).abs() < num(DIFF));
+            }
+        }
+    }
+    
+    #[test]
+    fn test_con_merge() {
+        let bodies = vec![
+            Body { pos: Vector::new(0, 0, 0), speed: Vector::new(1, 0, 0), mass: num(3), id: 0 },
+            Body { pos: Vector::new(4, 0, 0), speed: Vector::new(0, 2, 0), mass: num(1), id: 1 },
+            Body { pos: Vector::new(0, 5, 0), speed: Vector::new(0, 0, 0), mass: num(1), id: 2 },
+        ];
+        let mut con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        let (mass, momentum, center) = (con.total_mass(), con.momentum(), con.center_of_mass());
+        
+        let absorbed = con.merge(0, 1).unwrap();
+        assert_eq!(absorbed.id, 1);
+        assert_eq!(con.len(), 2);
+        assert!(con.get_body(1).is_none());
+        let merged = con.get_body(0).unwrap();
+        assert_eq!(merged.mass, num(4));
+        assert_eq!(merged.pos, Vector::new(1, 0, 0));
+        assert_eq!(merged.speed, Vector::new(0.75, 0.5, 0));
+        assert_eq!(con.total_mass(), mass);
+        assert_eq!(con.momentum(), momentum);
+        assert_eq!(con.center_of_mass(), center);
+        
+        assert!(con.merge(0, 1).is_none());
+        assert!(con.merge(2, 2).is_none());
+        con.update(num(0.001));
+    }
+    
+    fn bounce_pair(offset: Num) -> (Controller<Body>, Conserved) {
+        let bodies = vec![
+            Body { pos: Vector::new(0, 0, 0), speed: Vector::new(1, 0, 0), mass: num(2), id: 0 },
+            Body { pos: Vector::new(1, offset, 0), speed: Vector::new(-1, 0, 0), mass: num(1), id: 1 },
+        ];
+        let con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        let before = con.conserved();
+        (con, before)
+    }
+    
+    #[test]
+    fn test_con_bounce_head_on() {
+        let (mut con, before) = bounce_pair(num(0));
+        let kinetic = con.kinetic_energy();
+        assert!(con.bounce(0, 1, num(1), num(1)));
+        // Elastic: the speeds of a 2:1 head-on impact swap to -1/3 and 5/3.
+        assert!(con.get_body(0).unwrap().speed.distance(Vector::new(-1. / 3., 0, 0)) < DIFF);
+        assert!(con.get_body(1).unwrap().speed.distance(Vector::new(5. / 3., 0, 0)) < DIFF);
+        assert!((con.kinetic_energy() - kinetic).abs() < DIFF);
+        assert!((con.momentum() - before.momentum).module() < 1e-12);
+        // Separating bodies are left alone.
+        assert!(!con.bounce(0, 1, num(1), num(1)));
+        
+        let (mut con, before) = bounce_pair(num(0));
+        let kinetic = con.kinetic_energy();
+        assert!(con.bounce(0, 1, num(0.5), num(1)));
+        let after = con.get_body(1).unwrap().speed - con.get_body(0).unwrap().speed;
+        assert!(after.distance(Vector::new(1, 0, 0)) < DIFF, "relative speed {}", after);
+        // A loss of (1 - e^2) of the kinetic energy of the relative motion, 1/2 * 2/3 * 2^2.
+        let lost = (num(1) - num(0.25)) * num(4) / num(3);
+        assert!((kinetic - con.kinetic_energy() - lost).abs() < DIFF);
+        assert!((con.momentum() - before.momentum).module() < 1e-12);
+    }
+    
+    #[test]
+    fn test_con_bounce_glancing() {
+        // The line of centers 30 degrees off the line of motion, the spheres slightly overlapping.
+        let offset = num(0.5) / num(0.75).sqrt();
+        let (mut con, before) = bounce_pair(offset);
+        let center = con.center_of_mass();
+        let kinetic = con.kinetic_energy();
+        let normal = (con.get_body(1).unwrap().pos - con.get_body(0).unwrap().pos).unit();
+        let tangent = Vector::z_axis().cross_prod(&normal);
+        let tangential: Vec<Num> = con.iter().map(|x| x.speed.dot_prod(&tangent)).collect();
+        assert!(con.bounce(0, 1, num(1), num(1.2)));
+        
+        let (a, b) = (con.get_body(0).unwrap(), con.get_body(1).unwrap());
+        assert!((a.pos.distance(b.pos) - num(1.2)).abs() < DIFF);
+        assert!(con.center_of_mass().distance(center) < 1e-12);
+        assert!((a.speed.dot_prod(&tangent) - tangential[0]).abs() < DIFF);
+        assert!((b.speed.dot_prod(&tangent) - tangential[1]).abs() < DIFF);
+        assert!((b.speed - a.speed).dot_prod(&normal) > num(0));
+        assert!((con.kinetic_energy() - kinetic).abs() < DIFF);
+        assert!((con.momentum() - before.momentum).module() < 1e-12);
+    }
+    
+    #[test]
+    fn test_con_collect_each_other() {
+        // Bodies which can be test particles.
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        struct Body {
+            id: BodyId,
+            pos: Vector,
+            speed: Vector,
+            mass: Num,
+            test_particle: bool,
+        }
+        auto_impl_body!(pos, speed, mass, id, (), test_particle);
+        
+        let mut bodies: Vec<Body> = (0..4).map(|id| Body {
+            pos: Vector::new(id as f64, 0, 0),
+            speed: Vector::origin(),
+            mass: num(1),
+            id,
+            test_particle: false,
+        }).collect();
+        // Test particles are in no pair.
+        bodies.push(Body { pos: Vector::new(0.5, 0, 0), speed: Vector::origin(), mass: num(0), id: 4, test_particle: true });
+        let con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        let close = |a: &Body, b: &Body| if a.pos.distance(b.pos) <= num(1) {
+            IterStatus::Done((a.id, b.id))
+        } else {
+            IterStatus::Continue
+        };
+        assert_eq!(con.with_each_other(close), Some((0, 1)));
+        assert_eq!(con.collect_each_other(close), vec![(0, 1), (1, 2), (2, 3)]);
+    }
+    
+    #[test]
+    fn test_con_contact() {
+        // Without gravity, b closes in at 1000 and would tunnel through a within a single step.
+        let bodies = vec![
+            Body { pos: Vector::new(0, 0, 0), speed: Vector::origin(), mass: num(1), id: 0 },
+            Body { pos: Vector::new(10, 0.5, 0), speed: Vector::new(-1000, 0, 0), mass: num(1), id: 1 },
+        ];
+        let gravity = Gravity { constant: num(0), ..Default::default() };
+        let mut con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), gravity);
+        assert_eq!(con.update_until_contact(num(0.1), |_, _| num(1), num(1e-12)), Some((0, 1)));
+        // Touching at x = sqrt(1 - 0.5^2).
+        let time = (num(10) - num(0.75).sqrt()) / num(1000);
+        assert!((con.total_seconds() - time).abs() < 1e-11, "stopped at {}", con.total_seconds());
+        assert!((con.get_body(1).unwrap().pos.module() - num(1)).abs() < 1e-8);
+        
+        // Already touching, they pass through each other.
+        assert_eq!(con.update_until_contact(num(0.1), |_, _| num(1), num(1e-12)), None);
+        assert!((con.total_seconds() - time - num(0.1)).abs() < 1e-11);
+        // Far apart nothing happens.
+        assert_eq!(con.update_until_contact(num(-0.01), |_, _| num(1), num(1e-12)), None);
+    }
+    
+    #[test]
+    fn test_con_massless() {
+        // Massless bodies are no test particles unless they say so, and still touch.
+        let bodies = vec![
+            Body { pos: Vector::new(0, 0, 0), speed: Vector::new(1, 0, 0), mass: num(0), id: 0 },
+            Body { pos: Vector::new(10, 2, 0), speed: Vector::new(-1, 0, 0), mass: num(0), id: 1 },
+        ];
+        let mut con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        assert!(con.iter().all(|x| !x.is_test_particle()));
+        assert_eq!(con.center_of_mass(), Vector::new(5, 1, 0));
+        assert_eq!(con.center_of_mass_speed(), Vector::origin());
+        assert_eq!(con.update_until_contact(num(10), |_, _| num(3), num(1e-12)), Some((0, 1)));
+        
+        let empty: Controller<Body> = Controller::new(vec![], IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        assert_eq!(empty.center_of_mass(), Vector::origin());
+        assert_eq!(empty.center_of_mass_speed(), Vector::origin());
+    }
+    
+    #[test]
+    fn test_con_contact_third_body() {
+        // Two specks at rest, whose pull on each other is negligible, fall into each other within a step
+        // towards a body between them, which they stay far enough from.
+        let bodies = vec![
+            Body { pos: Vector::new(0, 0, 0), speed: Vector::origin(), mass: num(1e-9), id: 0 },
+            Body { pos: Vector::new(0.52, 0, 0), speed: Vector::origin(), mass: num(1e-9), id: 1 },
+            Body { pos: Vector::new(0.26, 0, 0), speed: Vector::origin(), mass: num(0.15), id: 2 },
+        ];
+        let contact = |a: &Body, b: &Body| if a.id == 2 || b.id == 2 { num(0.1) } else { num(0.5) };
+        let mut con = Controller::new(bodies, IntegratorKind::Leapfrog.build(StepControl::default()), Gravity::default());
+        assert_eq!(con.update_until_contact(num(0.1), contact, num(1e-12)), Some((0, 1)));
+        let distance = con.get_body(0).unwrap().pos.distance(con.get_body(1).unwrap().pos);
+        assert!((distance - num(0.5)).abs() < 1e-8, "stopped {} apart", distance);
+    }
+    
+    #[test]
+    fn test_con_contact_adaptive() {
+        // Falling into each other from rest, so that only their pull brings them into contact.
+        let bodies = vec![
+            Body { pos: Vector::new(-1, 0, 0), speed: Vector::origin(), mass: num(1), id: 0 },
+            Body { pos: Vector::new(1, 0, 0), speed: Vector::origin(), mass: num(1), id: 1 },
+        ];
+        // Long steps are allowed, so that the step size is not simply the cap.
+        let control = StepControl { max_delta: num(10), ..Default::default() };
+        let build = || IntegratorKind::Dopri45.build(control);
+        let mut con = Controller::new(bodies.clone(), build(), Gravity::default());
+        assert_eq!(con.update_until_contact(num(3), |_, _| num(0.5), num(1e-12)), Some((0, 1)));
+        assert!((con.get_body(0).unwrap().pos.distance(con.get_body(1).unwrap().pos) - num(0.5)).abs() < 1e-8);
+        
+        // The tries of the bisection leave the step size as it was, so the state is the one a plain update reaches.
+        let mut plain = Controller::new(bodies, build(), Gravity::default());
+        plain.update(con.total_seconds());
+        assert_eq!(plain.get_body(1), con.get_body(1));
+    }
 }
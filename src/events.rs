@@ -0,0 +1,320 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+use serde::{Deserialize, Serialize};
+use crate::body::{BodyId, BodyLike};
+use crate::controller::Controller;
+use crate::num::{num, Num};
+use crate::orbit::Elements;
+use crate::vector::Vector;
+
+/// Thresholds of the events to detect, from the `[events]` config table. Zero turns an event off.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Report the closest approach of two bodies passing nearer than this.
+    pub encounter_distance: Num,
+    /// Report bodies leaving on an unbound orbit once this far from the center of mass.
+    pub escape_radius: Num,
+    /// Report two bodies becoming bound, with an apoapsis below this share of the distance to any other body.
+    pub binary_ratio: Num,
+}
+
+impl Settings {
+    pub fn is_on(&self) -> bool {
+        self.encounter_distance > num(0) || self.escape_radius > num(0) || self.binary_ratio > num(0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EventKind {
+    /// Closest approach of `this` and `other`.
+    Encounter { distance: Num },
+    /// `this` leaving the others, with its two-body energy per unit mass relative to them.
+    Escape { energy: Num },
+    /// `this` and `other` now orbiting each other.
+    Binary { a: Num, e: Num },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Event {
+    pub time: Num,
+    pub this: BodyId,
+    pub other: Option<BodyId>,
+    pub kind: EventKind,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Encounter { .. } => "encounter",
+            EventKind::Escape { .. } => "escape",
+            EventKind::Binary { .. } => "binary",
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let other = self.other.map_or(String::new(), |x| x.to_string());
+        match self.kind {
+            EventKind::Encounter { distance } => write!(
+                f, "Close encounter of body {} and body {} at {:.4}, distance {:.4e}",
+                self.this, other, self.time, distance,
+            ),
+            EventKind::Escape { energy } => write!(
+                f, "Body {} escaping at {:.4}, energy {:.4e}",
+                self.this, self.time, energy,
+            ),
+            EventKind::Binary { a, e } => write!(
+                f, "Body {} and body {} form a binary at {:.4}, a {:.4e}, e {:.4}",
+                self.this, other, self.time, a, e,
+            ),
+        }
+    }
+}
+
+/// Write `events` as CSV, with the distance, energy or semi-major axis of each in `value`.
+pub fn write_csv<W: Write>(mut out: W, events: &[Event]) -> io::Result<()> {
+    writeln!(out, "time,event,this,other,value")?;
+    for event in events {
+        let value = match event.kind {
+            EventKind::Encounter { distance } => distance,
+            EventKind::Escape { energy } => energy,
+            EventKind::Binary { a, .. } => a,
+        };
+        let other = event.other.map_or(String::new(), |x| x.to_string());
+        writeln!(out, "{},{},{},{},{}", event.time, event.kind.name(), event.this, other, value)?;
+    }
+    out.flush()
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Approach {
+    distance: Num,
+    /// Distance and time of the closest sample so far, while nearer than the threshold.
+    closest: Option<(Num, Num)>,
+}
+
+/// Finds events by comparing successive states of a controller.
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    settings: Settings,
+    approaches: HashMap<(BodyId, BodyId), Approach>,
+    escaping: HashSet<BodyId>,
+    binaries: HashSet<(BodyId, BodyId)>,
+    started: bool,
+}
+
+impl Detector {
+    pub fn new(settings: Settings) -> Self {
+        Self { settings, ..Default::default() }
+    }
+    
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+    
+    /// Events of the massive bodies since the last check. Pairs bound at the first check are not reported as new binaries.
+    pub fn check<B: BodyLike>(&mut self, controller: &Controller<B>) -> Vec<Event> {
+        let mut events = Vec::new();
+        let time = controller.total_seconds();
+        let bodies: Vec<&B> = controller.iter().filter(|x| !x.is_test_particle()).collect();
+        let constant = controller.gravity().constant;
+        
+        for i in 0..bodies.len() {
+            for j in (i + 1)..bodies.len() {
+                let (a, b) = (bodies[i], bodies[j]);
+                let key = (*a.id(), *b.id());
+                let (this, other) = (*a.id(), Some(*b.id()));
+                
+                if self.settings.encounter_distance > num(0) {
+                    if let Some(distance) = self.approach(key, a.pos().distance(*b.pos()), time) {
+                        events.push(Event { time: distance.1, this, other, kind: EventKind::Encounter { distance: distance.0 } });
+                    }
+                }
+                
+                if self.settings.binary_ratio > num(0) {
+                    let elements = binary(&bodies, i, j, constant, self.settings.binary_ratio);
+                    match elements {
+                        Some(x) if self.binaries.insert(key) && self.started => {
+                            events.push(Event { time, this, other, kind: EventKind::Binary { a: x.a, e: x.e } });
+                        }
+                        Some(_) => {}
+                        None => { self.binaries.remove(&key); }
+                    }
+                }
+            }
+        }
+        
+        if self.settings.escape_radius > num(0) {
+            for i in 0..bodies.len() {
+                let id = *bodies[i].id();
+                match escape(&bodies, i, constant, self.settings.escape_radius) {
+                    Some(energy) => if self.escaping.insert(id) {
+                        events.push(Event { time, this: id, other: None, kind: EventKind::Escape { energy } });
+                    },
+                    None => { self.escaping.remove(&id); }
+                }
+            }
+        }
+        
+        self.started = true;
+        events
+    }
+    
+    /// Update the approach of a pair, returning its closest distance and time once the bodies move apart again.
+    fn approach(&mut self, key: (BodyId, BodyId), distance: Num, time: Num) -> Option<(Num, Num)> {
+        let threshold = self.settings.encounter_distance;
+        let approach = self.approaches.entry(key).or_insert(Approach { distance, closest: None });
+        let mut passed = None;
+        if distance > approach.distance {
+            passed = approach.closest.take();
+        } else if distance < threshold {
+            approach.closest = Some((distance, time));
+        }
+        approach.distance = distance;
+        passed
+    }
+}
+
+/// Orbit of bodies `i` and `j` around each other, if bound and well apart from every other body.
+fn binary<B: BodyLike>(bodies: &[&B], i: usize, j: usize, constant: Num, ratio: Num) -> Option<Elements> {
+    let (a, b) = (bodies[i], bodies[j]);
+    let mass = *a.mass() + *b.mass();
+    let elements = Elements::from_state(*b.pos() - *a.pos(), *b.speed() - *a.speed(), constant * mass);
+    if elements.a <= num(0) || elements.e >= num(1) {
+        return None;
+    }
+    let center = (*a.pos() * *a.mass() + *b.pos() * *b.mass()) / mass;
+    let nearest = bodies.iter().enumerate()
+        .filter(|(k, _)| *k != i && *k != j)
+        .map(|(_, x)| x.pos().distance(center))
+        .fold(Num::INFINITY, Num::min);
+    if elements.a * (num(1) + elements.e) < ratio * nearest { Some(elements) } else { None }
+}
+
+/// Energy per unit mass of body `i` relative to the rest of the system,
+/// if it is unbound and leaving beyond `radius` from the center of mass of the whole system.
+fn escape<B: BodyLike>(bodies: &[&B], i: usize, constant: Num, radius: Num) -> Option<Num> {
+    let this = bodies[i];
+    let (mut mass, mut moment, mut momentum) = (num(0), Vector::origin(), Vector::origin());
+    for (k, x) in bodies.iter().enumerate() {
+        if k != i {
+            mass += *x.mass();
+            moment += *x.pos() * *x.mass();
+            momentum += *x.speed() * *x.mass();
+        }
+    }
+    if mass <= num(0) {
+        return None;
+    }
+    let center = (moment + *this.pos() * *this.mass()) / (mass + *this.mass());
+    let pos = *this.pos() - moment / mass;
+    let speed = *this.speed() - momentum / mass;
+    let energy = speed.dot_prod(&speed) / num(2) - constant * (mass + *this.mass()) / pos.module();
+    let leaving = this.pos().distance(center) > radius && pos.dot_prod(&speed) > num(0);
+    if leaving && energy > num(0) { Some(energy) } else { None }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auto_impl_body;
+    use crate::gravity::Gravity;
+    use crate::integrator::IntegratorKind;
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Body {
+        id: BodyId,
+        pos: Vector,
+        speed: Vector,
+        mass: Num,
+    }
+    auto_impl_body!(pos, speed, mass, id, ());
+    
+    fn controller(bodies: Vec<Body>) -> Controller<Body> {
+        let gravity = Gravity { constant: num(1), ..Default::default() };
+        Controller::new(bodies, IntegratorKind::default().build(Default::default()), gravity)
+    }
+    
+    fn run(con: &mut Controller<Body>, detector: &mut Detector, steps: usize, delta: Num) -> Vec<Event> {
+        let mut events = detector.check(con);
+        for _ in 0..steps {
+            con.update(delta);
+            events.extend(detector.check(con));
+        }
+        events
+    }
+    
+    #[test]
+    fn test_events_encounter() {
+        // A light body flying past a heavy one, missing it by about 0.1.
+        let mut con = controller(vec![
+            Body { id: 0, pos: Vector::origin(), speed: Vector::origin(), mass: num(1) },
+            Body { id: 1, pos: Vector::new(-2, 0.1, 0), speed: Vector::new(10, 0, 0), mass: num(1e-6) },
+        ]);
+        let mut detector = Detector::new(Settings { encounter_distance: num(0.5), ..Default::default() });
+        let events = run(&mut con, &mut detector, 400, num(0.001));
+        assert_eq!(events.len(), 1, "{:?}", events);
+        let event = events[0];
+        assert_eq!((event.this, event.other), (0, Some(1)));
+        let EventKind::Encounter { distance } = event.kind else { panic!("{:?}", event) };
+        assert!(distance > num(0.09) && distance < num(0.1), "distance {}", distance);
+        assert!((event.time - num(0.2)).abs() < 0.01, "time {}", event.time);
+    }
+    
+    #[test]
+    fn test_events_escape() {
+        // Faster than the escape speed sqrt(2) from distance 1.
+        let mut con = controller(vec![
+            Body { id: 0, pos: Vector::origin(), speed: Vector::origin(), mass: num(1) },
+            Body { id: 1, pos: Vector::new(1, 0, 0), speed: Vector::new(0, 2, 0), mass: num(1e-6) },
+        ]);
+        let mut detector = Detector::new(Settings { escape_radius: num(3), ..Default::default() });
+        let events = run(&mut con, &mut detector, 3000, num(0.001));
+        assert_eq!(events.len(), 1, "{:?}", events);
+        assert_eq!((events[0].this, events[0].other), (1, None));
+        let EventKind::Escape { energy } = events[0].kind else { panic!("{:?}", events[0]) };
+        assert!((energy - num(1)).abs() < 1e-3, "energy {}", energy);
+        assert!(con.get_body(1).unwrap().pos().module() > num(3));
+    }
+    
+    #[test]
+    fn test_events_binary() {
+        // A pair flying apart, then captured.
+        let mut con = controller(vec![
+            Body { id: 0, pos: Vector::new(-0.1, 0, 0), speed: Vector::new(0, -2.5, 0), mass: num(0.5) },
+            Body { id: 1, pos: Vector::new(0.1, 0, 0), speed: Vector::new(0, 2.5, 0), mass: num(0.5) },
+            Body { id: 2, pos: Vector::new(10, 0, 0), speed: Vector::origin(), mass: num(0.5) },
+        ]);
+        let mut detector = Detector::new(Settings { binary_ratio: num(0.5), ..Default::default() });
+        assert!(run(&mut con, &mut detector, 100, num(0.001)).is_empty());
+        
+        // Slow the pair down to a bound orbit.
+        for id in [0, 1] {
+            let body = con.get_body_mut(id).unwrap();
+            body.speed *= num(0.1);
+        }
+        let events = detector.check(&con);
+        assert_eq!(events.len(), 1, "{:?}", events);
+        assert_eq!((events[0].this, events[0].other), (0, Some(1)));
+        let EventKind::Binary { a, e } = events[0].kind else { panic!("{:?}", events[0]) };
+        assert!(a > num(0) && e < num(1));
+        assert!(detector.check(&con).is_empty());
+    }
+    
+    #[test]
+    fn test_events_csv() {
+        let events = [
+            Event { time: num(1.5), this: 0, other: Some(2), kind: EventKind::Encounter { distance: num(0.25) } },
+            Event { time: num(2), this: 1, other: None, kind: EventKind::Escape { energy: num(0.5) } },
+        ];
+        let mut out = Vec::new();
+        write_csv(&mut out, &events).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,event,this,other,value\n1.5,encounter,0,2,0.25\n2,escape,1,,0.5\n",
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::body::{BodyId, BodyLike};
+use crate::controller::Controller;
+use crate::num::num;
+use crate::vector::Vector;
+
+/// Reference frame positions are shown in, set by the `frame` config key,
+/// e.g. `frame = "center_of_mass"`, `frame = { body = 0 }` or `frame = { co_rotating = [0, 1] }`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frame {
+    #[default]
+    Inertial,
+    /// Centered on the center of mass of all bodies.
+    CenterOfMass,
+    /// Centered on a body.
+    Body(BodyId),
+    /// Centered on the center of mass of a pair, turning with the line between them,
+    /// which is the X axis, in their orbital plane, which is the XY plane.
+    CoRotating(BodyId, BodyId),
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frame::Inertial => write!(f, "inertial"),
+            Frame::CenterOfMass => write!(f, "center of mass"),
+            Frame::Body(id) => write!(f, "body {}", id),
+            Frame::CoRotating(a, b) => write!(f, "co-rotating with bodies {} and {}", a, b),
+        }
+    }
+}
+
+/// Maps inertial positions into a frame, as it is at one instant.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    origin: Vector,
+    /// Axes of the frame in inertial coordinates, unless only shifted.
+    axes: Option<[Vector; 3]>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self { origin: Vector::origin(), axes: None }
+    }
+}
+
+impl Transform {
+    pub fn apply(&self, pos: Vector) -> Vector {
+        let diff = pos - self.origin;
+        match &self.axes {
+            Some([x, y, z]) => Vector::new(diff.dot_prod(x), diff.dot_prod(y), diff.dot_prod(z)),
+            None => diff,
+        }
+    }
+    
+    /// The inertial position that `apply` maps to `pos`.
+    pub fn unapply(&self, pos: Vector) -> Vector {
+        match &self.axes {
+            Some([x, y, z]) => self.origin + *x * pos.x() + *y * pos.y() + *z * pos.z(),
+            None => self.origin + pos,
+        }
+    }
+}
+
+impl Frame {
+    /// The frame as it is now. Frames of bodies that are gone fall back to the inertial one.
+    pub fn transform<B: BodyLike>(&self, controller: &Controller<B>) -> Transform {
+        match *self {
+            Frame::Inertial => Transform::default(),
+            Frame::CenterOfMass => Transform { origin: controller.center_of_mass(), axes: None },
+            Frame::Body(id) => controller.get_body(id)
+                .map_or_else(Transform::default, |x| Transform { origin: *x.pos(), axes: None }),
+            Frame::CoRotating(a, b) => match (controller.get_body(a), controller.get_body(b)) {
+                (Some(a), Some(b)) => co_rotating(a, b),
+                _ => Transform::default(),
+            },
+        }
+    }
+}
+
+fn co_rotating<B: BodyLike>(a: &B, b: &B) -> Transform {
+    let mass = *a.mass() + *b.mass();
+    let origin = if mass == num(0) {
+        a.pos().center_of(b.pos())
+    } else {
+        (*a.pos() * *a.mass() + *b.pos() * *b.mass()) / mass
+    };
+    let diff = *b.pos() - *a.pos();
+    let normal = diff.cross_prod(&(*b.speed() - *a.speed()));
+    // Without an orbital plane there is nothing to turn with.
+    if diff.is_zero() || normal.is_zero() {
+        return Transform { origin, axes: None };
+    }
+    let x = diff.unit();
+    let z = normal.unit();
+    Transform { origin, axes: Some([x, z.cross_prod(&x), z]) }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auto_impl_body;
+    use crate::gravity::Gravity;
+    use crate::integrator::IntegratorKind;
+    use crate::num::{sqrt, Num, PI};
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Body {
+        id: BodyId,
+        pos: Vector,
+        speed: Vector,
+        mass: Num,
+    }
+    auto_impl_body!(pos, speed, mass, id, ());
+    
+    /// A circular binary of separation 1 around the origin, and a test particle at its L4 point.
+    fn binary(drift: Vector) -> Controller<Body> {
+        let (m1, m2) = (num(1), num(0.01));
+        let omega = sqrt(m1 + m2);
+        let (x1, x2) = (-m2 / (m1 + m2), m1 / (m1 + m2));
+        let orbit = |x: Num, y: Num| Vector::new(-omega * y, omega * x, 0);
+        let l4 = Vector::new(x1 + num(0.5), sqrt(num(3)) / num(2), 0);
+        let bodies = vec![
+            Body { id: 0, pos: Vector::new(x1, 0, 0), speed: orbit(x1, num(0)) + drift, mass: m1 },
+            Body { id: 1, pos: Vector::new(x2, 0, 0), speed: orbit(x2, num(0)) + drift, mass: m2 },
+            Body { id: 2, pos: l4, speed: orbit(l4.x(), l4.y()) + drift, mass: num(0) },
+        ];
+        let gravity = Gravity { constant: num(1), ..Default::default() };
+        Controller::new(bodies, IntegratorKind::default().build(Default::default()), gravity)
+    }
+    
+    fn positions(con: &Controller<Body>, frame: Frame) -> Vec<Vector> {
+        let transform = frame.transform(con);
+        con.iter().map(|x| transform.apply(x.pos)).collect()
+    }
+    
+    #[test]
+    fn test_frame_center_of_mass() {
+        let drift = Vector::new(3, -1, 0.5);
+        let mut con = binary(drift);
+        let start = positions(&con, Frame::Body(0));
+        for _ in 0..1000 {
+            con.update(num(0.001));
+        }
+        // The system drifts away, its center stays put.
+        assert!(con.center_of_mass().distance(Vector::origin()) > num(3));
+        assert!(positions(&con, Frame::CenterOfMass).iter().all(|x| x.module() < num(1.1)));
+        assert_eq!(positions(&con, Frame::Body(0))[0], Vector::origin());
+        assert!(positions(&con, Frame::Body(0))[1].distance(start[1]) > num(0.5));
+        assert_eq!(Frame::Body(7).transform(&con), Transform::default());
+    }
+    
+    #[test]
+    fn test_frame_co_rotating() {
+        let mut con = binary(Vector::new(0.5, 0, 0));
+        let frame = Frame::CoRotating(0, 1);
+        let start = positions(&con, frame);
+        // Half an orbit, over which the pair turns around and the L4 point with it.
+        let steps = (PI / sqrt(num(1.01)) / num(0.001)) as usize;
+        for _ in 0..steps {
+            con.update(num(0.001));
+        }
+        let end = positions(&con, frame);
+        let transform = frame.transform(&con);
+        assert!(con.iter().all(|x| transform.unapply(transform.apply(x.pos)).distance(x.pos) < num(1e-12)));
+        assert!(end[1].distance(start[1]) < num(1e-3), "{} to {}", start[1], end[1]);
+        assert!(end[2].distance(start[2]) < num(1e-2), "{} to {}", start[2], end[2]);
+        assert!(end[2].distance(start[2]) < con.iter().nth(2).unwrap().pos.distance(start[2]));
+    }
+    
+    #[test]
+    fn test_frame_config() {
+        #[derive(Deserialize)]
+        struct Config {
+            frames: Vec<Frame>,
+        }
+        let config: Config = toml::from_str(r#"frames = ["inertial", "center_of_mass", { body = 2 }, { co_rotating = [0, 1] }]"#).unwrap();
+        assert_eq!(config.frames, vec![Frame::Inertial, Frame::CenterOfMass, Frame::Body(2), Frame::CoRotating(0, 1)]);
+    }
+}
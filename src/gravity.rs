@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use crate::body::BodyLike;
+use crate::num::{num, sqrt, square, Num};
+use crate::octree::Octree;
+use crate::vector::Vector;
+
+/// Below this many bodies the tree costs more than it saves.
+const DIRECT_MAX: usize = 64;
+/// Fewest bodies worth handing to another thread.
+const MIN_CHUNK: usize = 16;
+
+/// Newtonian gravity with Plummer softening.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Gravity {
+    pub constant: Num,
+    pub softening: Num,
+    /// Barnes-Hut opening angle, 0 sums over every pair.
+    pub theta: Num,
+    /// Worker threads computing the forces, 0 for one per CPU.
+    pub threads: usize,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self {
+            constant: num(1),
+            softening: num(0),
+            theta: num(0),
+            threads: 1,
+        }
+    }
+}
+
+impl Gravity {
+    /// Acceleration of a unit mass at offset `diff` from a body of `mass`, pointing towards the body.
+    pub fn pull(&self, diff: Vector, mass: Num) -> Vector {
+        let d2 = diff.dot_prod(&diff) + square(self.softening);
+        let d = sqrt(d2);
+        diff * (-self.constant * mass / (d2 * d))
+    }
+    
+    pub fn potential(&self, a: &impl BodyLike, b: &impl BodyLike) -> Num {
+        let d2 = square(a.pos().distance(*b.pos())) + square(self.softening);
+        -self.constant * a.mass() * b.mass() / sqrt(d2)
+    }
+    
+    /// Accelerations of `bodies`, pulled by the massive ones only, so test particles add O(N·M) work.
+    pub fn accelerations<B: BodyLike>(&self, bodies: &[B], acc: &mut Vec<Vector>) {
+        let massive = bodies.iter().filter(|x| !x.is_test_particle()).count();
+        if self.theta > num(0) && massive > DIRECT_MAX {
+            self.tree_accelerations(bodies, acc);
+        } else {
+            self.direct_accelerations(bodies, acc);
+        }
+    }
+    
+    /// Sum over every other massive body, in the order of `bodies`.
+    pub fn direct_accelerations<B: BodyLike>(&self, bodies: &[B], acc: &mut Vec<Vector>) {
+        let sources: Vec<usize> = (0..bodies.len()).filter(|&j| !bodies[j].is_test_particle()).collect();
+        self.each(acc, bodies.len(), |i| {
+            let pos = *bodies[i].pos();
+            let mut sum = Vector::origin();
+            for &j in &sources {
+                if j != i {
+                    sum += self.pull(pos - *bodies[j].pos(), *bodies[j].mass());
+                }
+            }
+            sum
+        });
+    }
+    
+    pub fn tree_accelerations<B: BodyLike>(&self, bodies: &[B], acc: &mut Vec<Vector>) {
+        let tree = Octree::build(bodies);
+        self.each(acc, bodies.len(), |i| tree.acceleration(i, self, self.theta));
+    }
+    
+    /// Worker threads to use for `len` bodies.
+    fn thread_count(&self, len: usize) -> usize {
+        let threads = if self.threads == 0 {
+            std::thread::available_parallelism().map_or(1, |x| x.get())
+        } else {
+            self.threads
+        };
+        threads.min(len.div_ceil(MIN_CHUNK)).max(1)
+    }
+    
+    /// Fill `acc` with `f` of every body, split into contiguous chunks over the worker threads.
+    /// Every body is computed on its own, so the result is the same for any number of threads.
+    fn each(&self, acc: &mut Vec<Vector>, len: usize, f: impl Fn(usize) -> Vector + Sync) {
+        acc.clear();
+        acc.resize(len, Vector::origin());
+        let threads = self.thread_count(len);
+        if threads == 1 {
+            for (i, a) in acc.iter_mut().enumerate() {
+                *a = f(i);
+            }
+            return;
+        }
+        
+        let chunk = len.div_ceil(threads);
+        let f = &f;
+        std::thread::scope(|scope| {
+            for (k, part) in acc.chunks_mut(chunk).enumerate() {
+                scope.spawn(move || {
+                    for (i, a) in part.iter_mut().enumerate() {
+                        *a = f(k * chunk + i);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auto_impl_body;
+    use crate::body::BodyId;
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Body {
+        id: BodyId,
+        pos: Vector,
+        speed: Vector,
+        mass: Num,
+        test_particle: bool,
+    }
+    auto_impl_body!(pos, speed, mass, id, (), test_particle);
+    
+    fn pair(distance: Num) -> Vec<Body> {
+        vec![
+            Body { id: 0, pos: Vector::origin(), speed: Vector::origin(), mass: num(2), test_particle: false },
+            Body { id: 1, pos: Vector::new(distance, 0, 0), speed: Vector::origin(), mass: num(3), test_particle: false },
+        ]
+    }
+    
+    #[test]
+    fn test_gravity_constant() {
+        let gravity = Gravity { constant: num(4), ..Default::default() };
+        let bodies = pair(num(2));
+        let mut acc = Vec::new();
+        gravity.accelerations(&bodies, &mut acc);
+        assert_eq!(acc[0], Vector::new(3, 0, 0));
+        assert_eq!(acc[1], Vector::new(-2, 0, 0));
+        assert_eq!(gravity.potential(&bodies[0], &bodies[1]), num(-12));
+    }
+    
+    #[test]
+    fn test_gravity_softening() {
+        let gravity = Gravity { softening: num(0.5), ..Default::default() };
+        let mut acc = Vec::new();
+        gravity.accelerations(&pair(num(0)), &mut acc);
+        assert_eq!(acc, vec![Vector::origin(), Vector::origin()]);
+        
+        // Far away the softened force approaches the Newtonian one.
+        gravity.accelerations(&pair(num(1000)), &mut acc);
+        let newton = num(3) / square(num(1000));
+        assert!((acc[0].x() - newton) / newton < num(1e-6));
+    }
+    
+    #[test]
+    fn test_gravity_threads() {
+        // A spiral of bodies, enough for many chunks and for the tree.
+        let bodies: Vec<Body> = (0..300).map(|id| {
+            let r = num(0.1) + num(id as f64) / num(100);
+            let phi = num(id as f64) * num(2.4);
+            let pos = Vector::new(r * phi.cos(), r * phi.sin(), num((id % 7) as f64) / num(50));
+            Body { id, pos, speed: Vector::origin(), mass: num(1) + num((id % 3) as f64), test_particle: false }
+        }).collect();
+        
+        for theta in [num(0), num(0.6)] {
+            let mut single = Vec::new();
+            Gravity { theta, ..Default::default() }.accelerations(&bodies, &mut single);
+            for threads in [0, 2, 3, 7, 64] {
+                let mut acc = Vec::new();
+                Gravity { theta, threads, ..Default::default() }.accelerations(&bodies, &mut acc);
+                assert_eq!(acc, single, "theta {} with {} threads", theta, threads);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_gravity_test_particles() {
+        let mut bodies = pair(num(2));
+        let particle = |id, x| Body { id, pos: Vector::new(x, 0, 0), speed: Vector::origin(), mass: num(0), test_particle: true };
+        // Coincident particles would pull each other infinitely hard, if they pulled at all.
+        bodies.extend([particle(2, num(4)), particle(3, num(4)), particle(4, num(-1))]);
+        let mut acc = Vec::new();
+        Gravity::default().accelerations(&bodies, &mut acc);
+        
+        let mut massive = Vec::new();
+        Gravity::default().accelerations(&pair(num(2)), &mut massive);
+        assert_eq!(acc[..2], massive[..]);
+        assert_eq!(acc[2], Vector::new(-num(2) / num(16) - num(3) / num(4), 0, 0));
+        assert_eq!(acc[3], acc[2]);
+        assert!(acc[4].distance(Vector::new(num(2) + num(3) / num(9), 0, 0)) < 1e-12);
+        
+        // Most of a large cloud of particles does not make it worth a tree.
+        let cloud: Vec<Body> = (0..500).map(|id| particle(id + 2, num(id as f64) / num(10) + num(5))).collect();
+        Gravity { theta: num(0.5), ..Default::default() }.accelerations(&[pair(num(2)), cloud].concat(), &mut acc);
+        assert_eq!(acc[..2], massive[..]);
+        assert!(acc[2..].iter().all(|x| x.x() < num(0) && x.y() == num(0)));
+    }
+}
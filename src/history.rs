@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use crate::body::BodyLike;
+use crate::controller::Controller;
+use crate::num::Num;
+
+/// The bodies as they were at one time, which a controller can go back to exactly.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Keyframe<B> {
+    pub time: Num,
+    pub bodies: Vec<B>,
+    /// Step an adaptive integrator had settled on, which it goes on from.
+    #[serde(default)]
+    pub carried_step: Option<Num>,
+}
+
+/// Keyframes of a run in time order, the oldest dropped once there are `capacity` of them.
+#[derive(Debug, Clone)]
+pub struct History<B> {
+    frames: VecDeque<Keyframe<B>>,
+    capacity: usize,
+}
+
+#[allow(dead_code)]
+impl<B: BodyLike> History<B> {
+    pub fn new(capacity: usize) -> Self {
+        Self { frames: VecDeque::new(), capacity: capacity.max(1) }
+    }
+    
+    /// Keep the state `controller` is in. Keyframes from its time on belong to a run
+    /// it branched off from, and are dropped, whose number is returned.
+    pub fn record(&mut self, controller: &Controller<B>) -> usize {
+        let keyframe = controller.keyframe();
+        let kept = self.frames.partition_point(|x| x.time < keyframe.time);
+        let dropped = self.frames.len() - kept;
+        self.frames.truncate(kept);
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(keyframe);
+        dropped
+    }
+    
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+    
+    /// Times of the first and the last keyframes.
+    pub fn span(&self) -> Option<(Num, Num)> {
+        Some((self.frames.front()?.time, self.frames.back()?.time))
+    }
+    
+    fn nearest_index(&self, time: Num) -> Option<usize> {
+        let after = self.frames.partition_point(|x| x.time < time);
+        match (after.checked_sub(1), self.frames.get(after)) {
+            (Some(before), Some(x)) => Some(if time - self.frames[before].time <= x.time - time { before } else { after }),
+            (Some(before), None) => Some(before),
+            (None, x) => x.map(|_| after),
+        }
+    }
+    
+    /// The keyframe closest to `time`.
+    pub fn nearest(&self, time: Num) -> Option<&Keyframe<B>> {
+        self.frames.get(self.nearest_index(time)?)
+    }
+    
+    /// The keyframe `offset` keyframes away from the one closest to `time`, clamped to the recorded ones.
+    pub fn step(&self, time: Num, offset: isize) -> Option<&Keyframe<B>> {
+        let index = self.nearest_index(time)?.saturating_add_signed(offset);
+        self.frames.get(index.min(self.frames.len() - 1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auto_impl_body;
+    use crate::body::BodyId;
+    use crate::gravity::Gravity;
+    use crate::integrator::{IntegratorKind, StepControl};
+    use crate::num::num;
+    use crate::vector::Vector;
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Body {
+        id: BodyId,
+        pos: Vector,
+        speed: Vector,
+        mass: Num,
+    }
+    auto_impl_body!(pos, speed, mass, id, ());
+    
+    fn controller() -> Controller<Body> {
+        controller_with(IntegratorKind::Rk4)
+    }
+    
+    fn controller_with(kind: IntegratorKind) -> Controller<Body> {
+        let bodies = vec![
+            Body { id: 0, pos: Vector::new(-1, 0, 0), speed: Vector::new(0, -0.3, 0.1), mass: num(1) },
+            Body { id: 1, pos: Vector::new(1, 0, 0), speed: Vector::new(0, 0.4, 0), mass: num(0.8) },
+            Body { id: 2, pos: Vector::new(0, 2, 0), speed: Vector::new(-0.2, 0, 0), mass: num(0.3) },
+        ];
+        let gravity = Gravity { constant: num(1), ..Default::default() };
+        // Tight enough for adaptive integrators to step below the updates, carrying their step over.
+        let control = StepControl { tolerance: num(1e-15), max_delta: num(1), ..Default::default() };
+        Controller::new(bodies, kind.build(control), gravity)
+    }
+    
+    /// Record `frames` keyframes, `steps` steps apart.
+    fn run(con: &mut Controller<Body>, history: &mut History<Body>, frames: usize, steps: usize) {
+        for _ in 0..frames {
+            for _ in 0..steps {
+                con.update(num(0.01));
+            }
+            history.record(con);
+        }
+    }
+    
+    #[test]
+    fn test_history_ring() {
+        let mut con = controller();
+        let mut history = History::new(8);
+        history.record(&con);
+        run(&mut con, &mut history, 10, 10);
+        assert_eq!(history.len(), 8);
+        let (first, last) = history.span().unwrap();
+        assert!((first - num(0.3)).abs() < 1e-9 && (last - num(1)).abs() < 1e-9, "{} to {}", first, last);
+        
+        assert_eq!(history.nearest(num(0)).unwrap().time, first);
+        assert!((history.nearest(num(0.64)).unwrap().time - num(0.6)).abs() < 1e-9);
+        assert!((history.nearest(num(0.66)).unwrap().time - num(0.7)).abs() < 1e-9);
+        assert_eq!(history.nearest(num(5)).unwrap().time, last);
+        assert!((history.step(num(0.6), -1).unwrap().time - num(0.5)).abs() < 1e-9);
+        assert_eq!(history.step(num(0.6), 10).unwrap().time, last);
+        assert_eq!(history.step(num(0.6), -10).unwrap().time, first);
+    }
+    
+    #[test]
+    fn test_history_exact() {
+        for kind in [IntegratorKind::Rk4, IntegratorKind::Dopri45] {
+            test_history_exact_with(kind);
+        }
+    }
+    
+    fn test_history_exact_with(kind: IntegratorKind) {
+        let mut con = controller_with(kind);
+        let mut history = History::new(100);
+        history.record(&con);
+        run(&mut con, &mut history, 10, 10);
+        let end = con.keyframe();
+        
+        // Integrating backward does not retrace the way forward ...
+        for _ in 0..50 {
+            con.update(num(-0.01));
+        }
+        let past = history.nearest(num(0.5)).unwrap().clone();
+        assert_ne!(con.keyframe().bodies, past.bodies, "{:?}", kind);
+        
+        // ... going back to a keyframe does, and so does running on from it.
+        con.restore(&past);
+        assert_eq!(con.keyframe(), past, "{:?}", kind);
+        for _ in 0..50 {
+            con.update(num(0.01));
+        }
+        assert_eq!(con.keyframe().bodies, end.bodies, "{:?}", kind);
+        
+        // Running on from a keyframe after changing course is a new branch.
+        con.restore(&past);
+        con.set_speed(2, Vector::origin());
+        assert_eq!(history.record(&con), 6);
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.span().unwrap().1, past.time);
+    }
+}
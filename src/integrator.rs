@@ -0,0 +1,324 @@
+use serde::{Deserialize, Serialize};
+use crate::body::BodyLike;
+use crate::num::{abs, max, min, num, pow, Num};
+use crate::vector::Vector;
+
+/// Writes the acceleration of every body into the buffer, in the same order as the bodies.
+pub type Field<'a, B> = &'a dyn Fn(&[B], &mut Vec<Vector>);
+
+pub trait Integrator<B: BodyLike> {
+    /// Advance `bodies` by `delta` of simulated time under `field`.
+    fn step(&mut self, bodies: &mut [B], delta: Num, field: Field<B>);
+    
+    /// The step size adaptive integrators carry from one step to the next, none for fixed step ones.
+    fn carried_step(&self) -> Option<Num> {
+        None
+    }
+    
+    /// Go back to a step size taken from `carried_step`, e.g. when a step is tried over.
+    #[allow(unused_variables)]
+    fn set_carried_step(&mut self, h: Option<Num>) {}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegratorKind {
+    #[default]
+    Euler,
+    #[serde(alias = "verlet")]
+    Leapfrog,
+    #[serde(alias = "yoshida4")]
+    Yoshida,
+    Rk4,
+    #[serde(alias = "rk45")]
+    Dopri45,
+}
+
+/// Error control of the adaptive integrators, ignored by fixed step ones.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StepControl {
+    pub tolerance: Num,
+    pub min_delta: Num,
+    pub max_delta: Num,
+}
+
+impl Default for StepControl {
+    fn default() -> Self {
+        Self {
+            tolerance: num(1e-8),
+            min_delta: num(1e-6),
+            max_delta: num(0.01),
+        }
+    }
+}
+
+impl IntegratorKind {
+    #[allow(dead_code)]
+    pub const ALL: [IntegratorKind; 5] = [
+        IntegratorKind::Euler,
+        IntegratorKind::Leapfrog,
+        IntegratorKind::Yoshida,
+        IntegratorKind::Rk4,
+        IntegratorKind::Dopri45,
+    ];
+    
+    pub fn build<B: BodyLike + 'static>(self, control: StepControl) -> Box<dyn Integrator<B>> {
+        match self {
+            IntegratorKind::Euler => Box::new(Euler::default()),
+            IntegratorKind::Leapfrog => Box::new(Leapfrog::default()),
+            IntegratorKind::Yoshida => Box::new(Yoshida::default()),
+            IntegratorKind::Rk4 => Box::new(Rk4::default()),
+            IntegratorKind::Dopri45 => Box::new(DormandPrince::new(control)),
+        }
+    }
+    
+    /// Adaptive integrators cover any `delta` with their own sub steps.
+    pub fn is_adaptive(self) -> bool {
+        matches!(self, IntegratorKind::Dopri45)
+    }
+}
+
+fn drift<B: BodyLike>(bodies: &mut [B], delta: Num) {
+    for body in bodies.iter_mut() {
+        let pos = *body.speed() * delta;
+        *body.pos_mut() += pos;
+    }
+}
+
+fn kick<B: BodyLike>(bodies: &mut [B], acc: &[Vector], delta: Num) {
+    for (body, a) in bodies.iter_mut().zip(acc) {
+        *body.speed_mut() += *a * delta;
+    }
+}
+
+/// Semi-implicit Euler: kick every body, then drift. First order.
+#[derive(Debug, Default)]
+pub struct Euler {
+    acc: Vec<Vector>,
+}
+
+impl<B: BodyLike> Integrator<B> for Euler {
+    fn step(&mut self, bodies: &mut [B], delta: Num, field: Field<B>) {
+        field(bodies, &mut self.acc);
+        kick(bodies, &self.acc, delta);
+        drift(bodies, delta);
+    }
+}
+
+/// Drift-kick-drift leapfrog, equivalent to velocity Verlet. Second order, symplectic.
+#[derive(Debug, Default)]
+pub struct Leapfrog {
+    acc: Vec<Vector>,
+}
+
+impl<B: BodyLike> Integrator<B> for Leapfrog {
+    fn step(&mut self, bodies: &mut [B], delta: Num, field: Field<B>) {
+        let half = delta / num(2);
+        drift(bodies, half);
+        field(bodies, &mut self.acc);
+        kick(bodies, &self.acc, delta);
+        drift(bodies, half);
+    }
+}
+
+/// Yoshida's fourth order composition of three leapfrog steps. Symplectic.
+#[derive(Debug, Default)]
+pub struct Yoshida {
+    acc: Vec<Vector>,
+}
+
+impl<B: BodyLike> Integrator<B> for Yoshida {
+    fn step(&mut self, bodies: &mut [B], delta: Num, field: Field<B>) {
+        let cbrt_2 = pow(num(2), num(1) / num(3));
+        let w1 = num(1) / (num(2) - cbrt_2);
+        let w0 = -cbrt_2 * w1;
+        let c = [w1 / num(2), (w0 + w1) / num(2), (w0 + w1) / num(2), w1 / num(2)];
+        let d = [w1, w0, w1];
+        
+        for (c, d) in c.iter().zip(d) {
+            drift(bodies, c * delta);
+            field(bodies, &mut self.acc);
+            kick(bodies, &self.acc, d * delta);
+        }
+        drift(bodies, c[3] * delta);
+    }
+}
+
+/// Classic fourth order Runge-Kutta. Not symplectic, but accurate per step.
+#[derive(Debug)]
+pub struct Rk4<B: BodyLike> {
+    probe: Vec<B>,
+    acc: Vec<Vector>,
+    dx: [Vec<Vector>; 4],
+    dv: [Vec<Vector>; 4],
+}
+
+impl<B: BodyLike> Default for Rk4<B> {
+    fn default() -> Self {
+        Self {
+            probe: Vec::new(),
+            acc: Vec::new(),
+            dx: Default::default(),
+            dv: Default::default(),
+        }
+    }
+}
+
+impl<B: BodyLike> Integrator<B> for Rk4<B> {
+    fn step(&mut self, bodies: &mut [B], delta: Num, field: Field<B>) {
+        let half = delta / num(2);
+        self.probe.clear();
+        self.probe.extend_from_slice(bodies);
+        
+        for k in 0..4 {
+            if k > 0 {
+                let h = if k == 3 { delta } else { half };
+                for (i, probe) in self.probe.iter_mut().enumerate() {
+                    *probe.pos_mut() = *bodies[i].pos() + self.dx[k - 1][i] * h;
+                }
+            }
+            field(&self.probe, &mut self.acc);
+            self.dx[k].clear();
+            self.dv[k].clear();
+            for (i, body) in bodies.iter().enumerate() {
+                let speed = if k == 0 {
+                    *body.speed()
+                } else {
+                    let h = if k == 3 { delta } else { half };
+                    *body.speed() + self.dv[k - 1][i] * h
+                };
+                self.dx[k].push(speed);
+                self.dv[k].push(self.acc[i]);
+            }
+        }
+        
+        let sixth = delta / num(6);
+        for (i, body) in bodies.iter_mut().enumerate() {
+            let dx = self.dx[0][i] + (self.dx[1][i] + self.dx[2][i]) * num(2) + self.dx[3][i];
+            let dv = self.dv[0][i] + (self.dv[1][i] + self.dv[2][i]) * num(2) + self.dv[3][i];
+            *body.pos_mut() += dx * sixth;
+            *body.speed_mut() += dv * sixth;
+        }
+    }
+}
+
+const DOPRI_A: [[Num; 6]; 7] = [
+    [0., 0., 0., 0., 0., 0.],
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [19372. / 6561., -25360. / 2187., 64448. / 6561., -212. / 729., 0., 0.],
+    [9017. / 3168., -355. / 33., 46732. / 5247., 49. / 176., -5103. / 18656., 0.],
+    [35. / 384., 0., 500. / 1113., 125. / 192., -2187. / 6784., 11. / 84.],
+];
+
+// Fifth order weights minus the embedded fourth order ones.
+const DOPRI_E: [Num; 7] = [
+    35. / 384. - 5179. / 57600.,
+    0.,
+    500. / 1113. - 7571. / 16695.,
+    125. / 192. - 393. / 640.,
+    -2187. / 6784. + 92097. / 339200.,
+    11. / 84. - 187. / 2100.,
+    -1. / 40.,
+];
+
+/// Dormand-Prince 5(4) with step size control. Each call advances exactly `delta`,
+/// split into as many sub steps as `control` demands.
+#[derive(Debug)]
+pub struct DormandPrince<B: BodyLike> {
+    control: StepControl,
+    h: Option<Num>,
+    probe: Vec<B>,
+    acc: Vec<Vector>,
+    kx: [Vec<Vector>; 7],
+    kv: [Vec<Vector>; 7],
+}
+
+impl<B: BodyLike> DormandPrince<B> {
+    pub fn new(control: StepControl) -> Self {
+        Self {
+            control,
+            h: None,
+            probe: Vec::new(),
+            acc: Vec::new(),
+            kx: Default::default(),
+            kv: Default::default(),
+        }
+    }
+    
+    /// Try a single step of `h`, leaving the result in `probe` and returning the scaled error.
+    fn attempt(&mut self, bodies: &[B], h: Num, field: Field<B>) -> Num {
+        self.probe.clear();
+        self.probe.extend_from_slice(bodies);
+        for (s, row) in DOPRI_A.iter().enumerate() {
+            for (i, (probe, body)) in self.probe.iter_mut().zip(bodies).enumerate() {
+                let mut dx = Vector::origin();
+                let mut dv = Vector::origin();
+                for (j, a) in row.iter().take(s).enumerate() {
+                    dx += self.kx[j][i] * *a;
+                    dv += self.kv[j][i] * *a;
+                }
+                *probe.pos_mut() = *body.pos() + dx * h;
+                *probe.speed_mut() = *body.speed() + dv * h;
+            }
+            field(&self.probe, &mut self.acc);
+            self.kx[s].clear();
+            self.kx[s].extend(self.probe.iter().map(|x| *x.speed()));
+            self.kv[s].clear();
+            self.kv[s].extend_from_slice(&self.acc);
+        }
+        
+        // The last stage is evaluated at the fifth order solution, which `probe` now holds.
+        let tolerance = self.control.tolerance;
+        let mut err = num(0);
+        for (i, (probe, body)) in self.probe.iter().zip(bodies).enumerate() {
+            let mut ex = Vector::origin();
+            let mut ev = Vector::origin();
+            for (j, e) in DOPRI_E.iter().enumerate() {
+                ex += self.kx[j][i] * *e;
+                ev += self.kv[j][i] * *e;
+            }
+            let sx = tolerance * (num(1) + max(body.pos().module(), probe.pos().module()));
+            let sv = tolerance * (num(1) + max(body.speed().module(), probe.speed().module()));
+            err = max(err, max((ex * h).module() / sx, (ev * h).module() / sv));
+        }
+        err
+    }
+}
+
+impl<B: BodyLike> Integrator<B> for DormandPrince<B> {
+    fn step(&mut self, bodies: &mut [B], delta: Num, field: Field<B>) {
+        let StepControl { min_delta, max_delta, .. } = self.control;
+        let sign = if delta < num(0) { num(-1) } else { num(1) };
+        let mut remaining = abs(delta);
+        let mut h = self.h.unwrap_or(max_delta);
+        while remaining > num(0) {
+            let tried = min(h, remaining);
+            let err = self.attempt(bodies, tried * sign, field);
+            let accepted = err <= num(1) || tried <= min_delta;
+            if accepted {
+                bodies.copy_from_slice(&self.probe);
+                remaining -= tried;
+            }
+            let factor = if err == num(0) {
+                num(5)
+            } else {
+                min(num(5), max(num(0.2), num(0.9) * pow(err, num(-0.2))))
+            };
+            // A step clipped to the end of `delta` says nothing about the natural step size.
+            if !accepted || tried == h || factor < num(1) {
+                h = min(max_delta, max(min_delta, tried * factor));
+            }
+        }
+        self.h = Some(h);
+    }
+    
+    fn carried_step(&self) -> Option<Num> {
+        self.h
+    }
+    
+    fn set_carried_step(&mut self, h: Option<Num>) {
+        self.h = h;
+    }
+}
@@ -3,9 +3,19 @@ extern crate lazy_static;
 
 mod body;
 mod controller;
+mod events;
+mod frame;
+mod gravity;
+mod history;
+mod integrator;
 mod num;
+mod octree;
+mod orbit;
+mod presets;
+mod simulate;
 mod apps;
 mod vector;
+mod units;
 mod config;
 
 
@@ -22,6 +32,15 @@ fn main() -> EResult<()> {
     #[cfg(feature = "color-eyre")]
     init().unwrap();
     
+    #[cfg(feature = "clap")]
+    if let Some(command) = config::command() {
+        return match command {
+            config::Command::Simulate(args) => args.run::<apps::Body>(),
+            #[cfg(feature = "gui-macroquad")]
+            config::Command::Render(args) => args.run(),
+        };
+    }
+    
     real_main()?;
     
     Ok(())
@@ -0,0 +1,225 @@
+use crate::body::BodyLike;
+use crate::gravity::Gravity;
+use crate::num::{abs, max, num, Num};
+use crate::vector::Vector;
+
+/// Levels of cells below the root at most, each half as wide as its parent.
+/// Bodies still sharing a cell at this depth stay together in one leaf, and pull each other directly.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+struct Node {
+    center: Vector,
+    half: Num,
+    mass: Num,
+    center_of_mass: Vector,
+    /// Range of the children in `Octree::nodes`, which are stored next to each other.
+    children: Option<(usize, usize)>,
+    /// Range into `Octree::order` of the bodies of a leaf.
+    bodies: (usize, usize),
+}
+
+impl Node {
+    fn contains(&self, pos: &Vector) -> bool {
+        let diff = *pos - self.center;
+        abs(diff.x()) <= self.half && abs(diff.y()) <= self.half && abs(diff.z()) <= self.half
+    }
+}
+
+/// Barnes-Hut octree over the positions of a set of bodies.
+#[derive(Debug, Clone, Default)]
+pub struct Octree {
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+    pos: Vec<Vector>,
+    mass: Vec<Num>,
+}
+
+fn octant(pos: &Vector, center: &Vector) -> usize {
+    (pos.x() > center.x()) as usize
+        | ((pos.y() > center.y()) as usize) << 1
+        | ((pos.z() > center.z()) as usize) << 2
+}
+
+impl Octree {
+    /// The tree holds the massive bodies only, test particles merely look it up.
+    pub fn build<B: BodyLike>(bodies: &[B]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            order: (0..bodies.len()).filter(|&i| !bodies[i].is_test_particle()).collect(),
+            pos: bodies.iter().map(|x| *x.pos()).collect(),
+            mass: bodies.iter().map(|x| *x.mass()).collect(),
+        };
+        if tree.order.is_empty() {
+            return tree;
+        }
+        
+        let (mut low, mut high) = (tree.pos[tree.order[0]], tree.pos[tree.order[0]]);
+        for pos in tree.order.iter().map(|&i| &tree.pos[i]) {
+            low = Vector::new(low.x().min(pos.x()), low.y().min(pos.y()), low.z().min(pos.z()));
+            high = Vector::new(high.x().max(pos.x()), high.y().max(pos.y()), high.z().max(pos.z()));
+        }
+        let size = high - low;
+        let half = max(max(size.x(), size.y()), max(size.z(), num(1e-12))) / num(2);
+        tree.nodes.push(tree.node(low.center_of(&high), half, 0, tree.order.len()));
+        tree.split(0, 0);
+        tree
+    }
+    
+    fn node(&self, center: Vector, half: Num, start: usize, end: usize) -> Node {
+        let mut mass = num(0);
+        let mut moment = Vector::origin();
+        for &i in &self.order[start..end] {
+            mass += self.mass[i];
+            moment += self.pos[i] * self.mass[i];
+        }
+        let center_of_mass = if mass == num(0) { center } else { moment / mass };
+        Node { center, half, mass, center_of_mass, children: None, bodies: (start, end) }
+    }
+    
+    fn split(&mut self, index: usize, depth: usize) {
+        let Node { center, half, bodies: (start, end), .. } = self.nodes[index];
+        if end - start <= 1 || depth >= MAX_DEPTH {
+            return;
+        }
+        
+        let pos = &self.pos;
+        self.order[start..end].sort_by_key(|&i| octant(&pos[i], &center));
+        let first = self.nodes.len();
+        let mut begin = start;
+        for k in 0..8 {
+            let count = self.order[begin..end].iter()
+                .take_while(|&&i| octant(&self.pos[i], &center) == k)
+                .count();
+            if count == 0 {
+                continue;
+            }
+            let sign = |bit: usize| if k & bit == 0 { -half / num(2) } else { half / num(2) };
+            let child_center = center + Vector::new(sign(1), sign(2), sign(4));
+            let child = self.node(child_center, half / num(2), begin, begin + count);
+            self.nodes.push(child);
+            begin += count;
+        }
+        let last = self.nodes.len();
+        self.nodes[index].children = Some((first, last));
+        for child in first..last {
+            self.split(child, depth + 1);
+        }
+    }
+    
+    /// Acceleration of body `i`, treating any cell that looks smaller than `theta` from it as a point mass.
+    pub fn acceleration(&self, i: usize, gravity: &Gravity, theta: Num) -> Vector {
+        let pos = self.pos[i];
+        let mut acc = Vector::origin();
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            match node.children {
+                Some((first, last)) => {
+                    let d = pos.distance(node.center_of_mass);
+                    if !node.contains(&pos) && num(2) * node.half < theta * d {
+                        acc += gravity.pull(pos - node.center_of_mass, node.mass);
+                    } else {
+                        stack.extend(first..last);
+                    }
+                }
+                None => {
+                    for &j in &self.order[node.bodies.0..node.bodies.1] {
+                        if j != i {
+                            acc += gravity.pull(pos - self.pos[j], self.mass[j]);
+                        }
+                    }
+                }
+            }
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auto_impl_body;
+    use crate::body::BodyId;
+    use crate::num::sqrt;
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Body {
+        id: BodyId,
+        pos: Vector,
+        speed: Vector,
+        mass: Num,
+        test_particle: bool,
+    }
+    auto_impl_body!(pos, speed, mass, id, (), test_particle);
+    
+    /// A reproducible cloud of bodies, denser towards the center.
+    fn cloud(n: usize) -> Vec<Body> {
+        let mut seed: u64 = 42;
+        let mut random = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as Num / (1u64 << 53) as Num
+        };
+        (0..n).map(|id| {
+            let dir = Vector::new(random() - 0.5, random() - 0.5, random() - 0.5);
+            let pos = dir * (random() * random() * num(4));
+            Body { id, pos, speed: Vector::origin(), mass: num(0.5) + random(), test_particle: false }
+        }).collect()
+    }
+    
+    /// Root mean square and maximum of the relative errors against direct summation.
+    fn errors(bodies: &[Body], theta: Num) -> (Num, Num) {
+        let gravity = Gravity { theta, ..Default::default() };
+        let mut exact = Vec::new();
+        let mut approx = Vec::new();
+        gravity.direct_accelerations(bodies, &mut exact);
+        gravity.tree_accelerations(bodies, &mut approx);
+        let relative: Vec<Num> = exact.iter().zip(&approx)
+            .map(|(a, b)| a.distance(*b) / a.module())
+            .collect();
+        let rms = sqrt(relative.iter().map(|x| x * x).sum::<Num>() / num(relative.len() as f64));
+        (rms, relative.iter().copied().fold(num(0), max))
+    }
+    
+    #[test]
+    fn test_octree_accuracy() {
+        let bodies = cloud(1000);
+        let (rms, worst) = errors(&bodies, num(0.5));
+        assert!(rms < 1e-2 && worst < 5e-2, "theta 0.5: rms {}, max {}", rms, worst);
+        let (rms_fine, _) = errors(&bodies, num(0.2));
+        assert!(rms_fine < rms / num(4), "theta 0.2: rms {}", rms_fine);
+        let (rms_exact, _) = errors(&bodies, num(0));
+        assert!(rms_exact < 1e-12, "theta 0: rms {}", rms_exact);
+    }
+    
+    #[test]
+    fn test_octree_test_particles() {
+        // Particles among the massive bodies, and further out.
+        let mut bodies = cloud(200);
+        let particles: Vec<Body> = cloud(300).into_iter()
+            .map(|x| Body { id: x.id + 200, pos: x.pos * num(1.5), mass: num(0), test_particle: true, ..x })
+            .collect();
+        bodies.extend(particles);
+        
+        let (rms, _) = errors(&bodies, num(0));
+        assert!(rms < 1e-12, "theta 0: rms {}", rms);
+        let (rms, worst) = errors(&bodies, num(0.5));
+        assert!(rms < 1e-2 && worst < 1e-1, "theta 0.5: rms {}, max {}", rms, worst);
+    }
+    
+    #[test]
+    fn test_octree_coincident() {
+        let mut bodies = cloud(100);
+        bodies[1].pos = bodies[0].pos;
+        let gravity = Gravity { softening: num(0.01), theta: num(0.5), ..Default::default() };
+        let mut acc = Vec::new();
+        gravity.accelerations(&bodies, &mut acc);
+        assert!(acc.iter().all(|x| x.module().is_finite()));
+        
+        // Few bodies are summed directly, whatever theta.
+        let mut direct = Vec::new();
+        gravity.accelerations(&bodies[..10], &mut acc);
+        gravity.direct_accelerations(&bodies[..10], &mut direct);
+        assert_eq!(acc, direct);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::num::{acos, cos, num, sin, sqrt, Num, PI};
+use crate::vector::Vector;
+
+const EPSILON: Num = 1e-11;
+
+/// Keplerian orbital elements, angles in radians.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Elements {
+    /// Semi-major axis, negative for hyperbolic orbits.
+    pub a: Num,
+    /// Eccentricity.
+    pub e: Num,
+    /// Inclination.
+    pub i: Num,
+    /// Argument of periapsis.
+    pub omega: Num,
+    /// Longitude of the ascending node.
+    pub node: Num,
+    /// True anomaly.
+    pub nu: Num,
+}
+
+fn angle_between(a: &Vector, b: &Vector) -> Num {
+    let cos_theta = a.dot_prod(b) / (a.module() * b.module());
+    acos(cos_theta.clamp(num(-1), num(1)))
+}
+
+impl Elements {
+    /// Position and speed relative to the parent, with `mu` = G * (parent mass + body mass).
+    pub fn to_state(self, mu: Num) -> (Vector, Vector) {
+        let p = self.a * (num(1) - self.e * self.e);
+        let r = p / (num(1) + self.e * cos(self.nu));
+        let pos = Vector::new(r * cos(self.nu), r * sin(self.nu), 0);
+        let speed = Vector::new(-sin(self.nu), self.e + cos(self.nu), 0) * sqrt(mu / p);
+        
+        let rotate = |v: Vector| v
+            .rotate(&Vector::z_axis(), self.omega)
+            .rotate(&Vector::x_axis(), self.i)
+            .rotate(&Vector::z_axis(), self.node);
+        (rotate(pos), rotate(speed))
+    }
+    
+    /// Whether the elements describe an orbit: elliptic with a positive `a`, or hyperbolic with a negative one
+    /// and a true anomaly between the asymptotes.
+    pub fn check(&self) -> Result<(), String> {
+        if !self.a.is_finite() || !self.e.is_finite() {
+            Err(format!("Invalid orbit of a = {} and e = {}", self.a, self.e))
+        } else if self.e < num(0) {
+            Err(format!("Eccentricity must not be negative, got {}", self.e))
+        } else if self.e == num(1) {
+            Err("Parabolic orbits are not supported".into())
+        } else if self.e < num(1) && self.a <= num(0) {
+            Err(format!("An orbit of eccentricity {} needs a positive a, got {}", self.e, self.a))
+        } else if self.e > num(1) && self.a >= num(0) {
+            Err(format!("An orbit of eccentricity {} needs a negative a, got {}", self.e, self.a))
+        } else if num(1) + self.e * cos(self.nu) <= num(0) {
+            Err(format!("True anomaly {} is beyond the asymptotes of the orbit", self.nu))
+        } else {
+            Ok(())
+        }
+    }
+    
+    /// Osculating elements of a relative state. Undefined angles of circular or equatorial orbits are zero.
+    pub fn from_state(pos: Vector, speed: Vector, mu: Num) -> Self {
+        let r = pos.module();
+        let h = pos.cross_prod(&speed);
+        let node_line = Vector::z_axis().cross_prod(&h);
+        let e_vec = (pos * (speed.dot_prod(&speed) - mu / r) - speed * pos.dot_prod(&speed)) / mu;
+        let e = e_vec.module();
+        let a = num(1) / (num(2) / r - speed.dot_prod(&speed) / mu);
+        let i = angle_between(&h, &Vector::z_axis());
+        
+        let equatorial = node_line.module() < EPSILON * h.module();
+        let circular = e < EPSILON;
+        // Reference direction of the orbit plane the angles are measured from.
+        let reference = if equatorial { Vector::x_axis() } else { node_line };
+        let node = if equatorial {
+            num(0)
+        } else {
+            let node = angle_between(&Vector::x_axis(), &node_line);
+            if node_line.y() < num(0) { num(2) * PI - node } else { node }
+        };
+        // Angles grow along the motion, i.e. counterclockwise seen from `h`.
+        let directed = |from: &Vector, to: &Vector| {
+            let theta = angle_between(from, to);
+            if from.cross_prod(to).dot_prod(&h) < num(0) { num(2) * PI - theta } else { theta }
+        };
+        let (omega, nu) = if circular {
+            (num(0), directed(&reference, &pos))
+        } else {
+            (directed(&reference, &e_vec), directed(&e_vec, &pos))
+        };
+        
+        Self { a, e, i, omega, node, nu }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    fn close(x: Num, y: Num) -> bool {
+        (x - y).abs() < 1e-9
+    }
+    
+    fn close_vector(x: Vector, y: Vector) -> bool {
+        x.distance(y) < 1e-9
+    }
+    
+    #[test]
+    fn test_orbit_circular() {
+        let elements = Elements { a: num(2), ..Default::default() };
+        let (pos, speed) = elements.to_state(num(8));
+        assert!(close_vector(pos, Vector::new(2, 0, 0)));
+        assert!(close_vector(speed, Vector::new(0, 2, 0)));
+        
+        let back = Elements::from_state(pos, speed, num(8));
+        assert!(close(back.a, num(2)));
+        assert!(close(back.e, num(0)));
+        assert!(close(back.nu, num(0)));
+    }
+    
+    #[test]
+    fn test_orbit_check() {
+        assert!(Elements { a: num(1), e: num(0.5), ..Default::default() }.check().is_ok());
+        assert!(Elements { a: num(-1), e: num(1.5), ..Default::default() }.check().is_ok());
+        assert!(Elements { a: num(1), e: num(1.5), ..Default::default() }.check().is_err());
+        assert!(Elements { a: num(-1), e: num(0.5), ..Default::default() }.check().is_err());
+        assert!(Elements { a: num(1), e: num(1), ..Default::default() }.check().is_err());
+        assert!(Elements { a: num(1), e: num(-0.1), ..Default::default() }.check().is_err());
+        assert!(Elements { a: num(-1), e: num(2), nu: num(2.5), ..Default::default() }.check().is_err());
+    }
+    
+    #[test]
+    fn test_orbit_round_trip() {
+        let elements = Elements {
+            a: num(1.5),
+            e: num(0.3),
+            i: num(0.4),
+            omega: num(1.1),
+            node: num(2.5),
+            nu: num(4.0),
+        };
+        let (pos, speed) = elements.to_state(num(3));
+        let back = Elements::from_state(pos, speed, num(3));
+        assert!(close(back.a, elements.a));
+        assert!(close(back.e, elements.e));
+        assert!(close(back.i, elements.i));
+        assert!(close(back.omega, elements.omega));
+        assert!(close(back.node, elements.node));
+        assert!(close(back.nu, elements.nu));
+    }
+}
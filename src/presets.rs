@@ -0,0 +1,148 @@
+/// A well known scenario, shipped as a config.
+#[derive(Debug, Copy, Clone)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub config: &'static str,
+}
+
+macro_rules! preset {
+    ($name: literal, $description: literal) => {
+        Preset {
+            name: $name,
+            description: $description,
+            config: include_str!(concat!("../examples/presets/", $name, ".toml")),
+        }
+    };
+}
+
+pub static PRESETS: &[Preset] = &[
+    preset!("figure8", "Figure-eight choreography of Chenciner and Montgomery"),
+    preset!("lagrange", "Lagrange's equilateral triangle"),
+    preset!("euler", "Euler's collinear configuration"),
+    preset!("pythagorean", "Burrau's Pythagorean 3-4-5 problem"),
+    preset!("broucke_a2", "Broucke A2, Broucke-Hadjidemetriou-Hénon family"),
+    preset!("broucke_a7", "Broucke A7, Broucke-Hadjidemetriou-Hénon family"),
+    preset!("broucke_r1", "Broucke R1, Broucke-Hadjidemetriou-Hénon family"),
+    preset!("sun_earth_moon", "The Sun, the Earth and the Moon in real units"),
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|x| x.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+    use crate::auto_impl_body;
+    use crate::body::{BodyId, BodyLike};
+    use crate::controller::Controller;
+    use crate::gravity::Gravity;
+    use crate::integrator::{IntegratorKind, StepControl};
+    use crate::num::{num, Num, PI};
+    use crate::vector::Vector;
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+    struct Body {
+        #[serde(default)]
+        id: BodyId,
+        #[serde(default)]
+        pos: Vector,
+        #[serde(default)]
+        speed: Vector,
+        mass: Num,
+    }
+    auto_impl_body!(pos, speed, mass, id, ());
+    
+    #[derive(Deserialize)]
+    struct Config {
+        bodies: Vec<Body>,
+        delta: Num,
+        #[serde(default)]
+        integrator: IntegratorKind,
+        gravitational_constant: Num,
+    }
+    
+    fn load(name: &str) -> (Controller<Body>, Num) {
+        let config: Config = crate::config::parse_config(find(name).unwrap().config).unwrap();
+        let bodies = config.bodies.into_iter()
+            .enumerate()
+            .map(|(id, body)| Body { id, ..body })
+            .collect();
+        let gravity = Gravity { constant: config.gravitational_constant, ..Default::default() };
+        let control = StepControl { tolerance: num(1e-12), min_delta: num(1e-9), ..Default::default() };
+        (Controller::new(bodies, config.integrator.build(control), gravity), config.delta)
+    }
+    
+    fn run(con: &mut Controller<Body>, delta: Num, time: Num) {
+        let steps = (time / delta).round() as usize;
+        let delta = time / num(steps as f64);
+        for _ in 0..steps {
+            con.update(delta);
+        }
+    }
+    
+    /// Run a periodic preset for one period and check every body is back where it started.
+    fn check_period(name: &str, period: Num, precision: Num) {
+        let (mut con, delta) = load(name);
+        let start: Vec<Body> = con.iter().copied().collect();
+        run(&mut con, delta, period);
+        for (a, b) in con.iter().zip(&start) {
+            let diff = a.pos.distance(b.pos);
+            assert!(diff < precision, "{}: body {} is {} away after a period", name, a.id, diff);
+        }
+        assert!(con.drift().energy < num(1e-8), "{}: energy drift {}", name, con.drift().energy);
+    }
+    
+    #[test]
+    fn test_presets_load() {
+        for preset in PRESETS {
+            let (con, _) = load(preset.name);
+            assert!(con.len() >= 3, "{} has {} bodies", preset.name, con.len());
+            assert!(con.momentum().module() < num(1e-8), "{} is not at rest", preset.name);
+        }
+    }
+    
+    #[test]
+    fn test_presets_figure8() {
+        check_period("figure8", num(6.32591398), num(1e-3));
+    }
+    
+    #[test]
+    fn test_presets_lagrange() {
+        check_period("lagrange", num(2) * PI, num(1e-3));
+    }
+    
+    #[test]
+    fn test_presets_euler() {
+        check_period("euler", num(2) * PI / num(1.25).sqrt(), num(1e-3));
+    }
+    
+    #[test]
+    fn test_presets_broucke() {
+        check_period("broucke_a2", num(7.70216), num(1e-3));
+        check_period("broucke_a7", num(12.05627), num(1e-3));
+        check_period("broucke_r1", num(5.22656), num(1e-3));
+    }
+    
+    #[test]
+    fn test_presets_pythagorean() {
+        let (mut con, delta) = load("pythagorean");
+        assert!((con.total_energy() - num(-769) / num(60)).abs() < num(1e-12));
+        run(&mut con, delta * num(100), num(10));
+        assert!(con.drift().energy < num(1e-8), "energy drift {}", con.drift().energy);
+    }
+    
+    #[test]
+    fn test_presets_sun_earth_moon() {
+        let (mut con, delta) = load("sun_earth_moon");
+        let start: Vec<Body> = con.iter().copied().collect();
+        run(&mut con, delta, num(365.25082));
+        let [sun, earth, moon] = [0, 1, 2].map(|x| *con.get_body(x).unwrap());
+        let diff = earth.pos.distance(start[1].pos);
+        assert!(diff < num(1e-3), "the Earth is {} away after a year", diff);
+        assert!(sun.pos.distance(start[0].pos) < num(1e-5));
+        assert!((moon.pos.distance(earth.pos) - num(0.00256955529)).abs() < num(1e-4));
+    }
+}
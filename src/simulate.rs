@@ -0,0 +1,383 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::body::BodyLike;
+use crate::config::EResult;
+use crate::controller::Controller;
+use crate::events::{self, Detector, Event};
+use crate::gravity::Gravity;
+use crate::integrator::{IntegratorKind, StepControl};
+use crate::num::{num, Num};
+
+fn default_delta() -> Num {
+    num(0.001)
+}
+
+fn default_gravitational_constant() -> Num {
+    num(1)
+}
+
+fn default_threads() -> usize {
+    Gravity::default().threads
+}
+
+fn default_tolerance() -> Num {
+    StepControl::default().tolerance
+}
+
+fn default_min_delta() -> Num {
+    StepControl::default().min_delta
+}
+
+fn default_max_delta() -> Num {
+    StepControl::default().max_delta
+}
+
+/// How bodies are moved, the settings every frontend shares.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Physics {
+    #[serde(default = "default_delta")]
+    pub delta: Num,
+    #[serde(default)]
+    pub integrator: IntegratorKind,
+    #[serde(default = "default_gravitational_constant")]
+    pub gravitational_constant: Num,
+    #[serde(default)]
+    pub softening: Num,
+    #[serde(default)]
+    pub theta: Num,
+    #[serde(default = "default_threads")]
+    pub threads: usize,
+    #[serde(default = "default_tolerance")]
+    pub tolerance: Num,
+    #[serde(default = "default_min_delta")]
+    pub min_delta: Num,
+    #[serde(default = "default_max_delta")]
+    pub max_delta: Num,
+    #[serde(default)]
+    pub events: events::Settings,
+}
+
+impl Physics {
+    pub fn gravity(&self) -> Gravity {
+        Gravity {
+            constant: self.gravitational_constant,
+            softening: self.softening,
+            theta: self.theta,
+            threads: self.threads,
+        }
+    }
+    
+    pub fn step_control(&self) -> StepControl {
+        StepControl {
+            tolerance: self.tolerance,
+            min_delta: self.min_delta,
+            max_delta: self.max_delta,
+        }
+    }
+}
+
+/// The frontend independent part of a config: bodies and how to move them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario<B> {
+    pub bodies: Vec<B>,
+    #[serde(flatten)]
+    pub physics: Physics,
+    /// Simulated time to start from, as saved in snapshots.
+    #[serde(default)]
+    pub time: Num,
+    /// What happens when bodies touch, which only the window handles.
+    pub collision: Option<String>,
+}
+
+impl<B: BodyLike + 'static> Scenario<B> {
+    /// Collisions are left to the window, bodies can only pass through each other here.
+    pub fn check(&self) -> EResult<()> {
+        match self.collision.as_deref() {
+            None | Some("ignore") => Ok(()),
+            Some(x) => anyhow::bail!(
+                "Collision mode {:?} is only supported in the window, set collision = \"ignore\" to simulate without it", x,
+            ),
+        }
+    }
+    
+    pub fn controller(self) -> Controller<B> {
+        let integrator = self.physics.integrator.build(self.physics.step_control());
+        let mut controller = Controller::new(self.bodies, integrator, self.physics.gravity());
+        controller.set_time(self.time);
+        controller
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Format {
+    Csv,
+    Ndjson,
+}
+
+impl Format {
+    /// Guess from the extension of the output file, CSV unless it looks like JSON lines.
+    pub fn of_file(name: &str) -> Self {
+        if name.ends_with(".ndjson") || name.ends_with(".jsonl") {
+            Format::Ndjson
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+/// Writes the state of every body at each sample, one line per body.
+pub struct Sampler<W: Write> {
+    out: W,
+    format: Format,
+}
+
+fn json_num(x: Num) -> String {
+    if x.is_finite() { x.to_string() } else { "null".into() }
+}
+
+impl<W: Write> Sampler<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<Self> {
+        if format == Format::Csv {
+            writeln!(out, "time,id,x,y,z,vx,vy,vz")?;
+        }
+        Ok(Self { out, format })
+    }
+    
+    pub fn sample<'a, B: BodyLike + 'a>(&mut self, time: Num, bodies: impl Iterator<Item=&'a B>) -> io::Result<()> {
+        for body in bodies {
+            let (x, y, z) = body.pos().to_tuple();
+            let (vx, vy, vz) = body.speed().to_tuple();
+            match self.format {
+                Format::Csv => writeln!(
+                    self.out, "{},{},{},{},{},{},{},{}",
+                    time, body.id(), x, y, z, vx, vy, vz,
+                )?,
+                Format::Ndjson => writeln!(
+                    self.out, "{{\"time\":{},\"id\":{},\"pos\":[{},{},{}],\"speed\":[{},{},{}]}}",
+                    json_num(time), body.id(),
+                    json_num(x), json_num(y), json_num(z),
+                    json_num(vx), json_num(vy), json_num(vz),
+                )?,
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Run `controller` for `duration` of simulated time, sampling every `interval` from the start,
+/// and at the end after a last shorter interval if `duration` is no multiple of `interval`.
+/// Fixed step integrators shrink `delta` slightly so that samples land exactly on the interval.
+/// The `detector`, if any, checks every step and the events it found are returned.
+pub fn run<B: BodyLike, W: Write>(
+    controller: &mut Controller<B>,
+    delta: Num,
+    adaptive: bool,
+    duration: Num,
+    interval: Num,
+    sampler: &mut Sampler<W>,
+    mut detector: Option<&mut Detector>,
+) -> io::Result<Vec<Event>> {
+    let start = controller.total_seconds();
+    let samples = (duration / interval + num(1e-9)).floor() as usize;
+    // Length of each interval, with the time sampled at its end.
+    let mut spans: Vec<(Num, Num)> = (1..=samples)
+        .map(|k| (interval, start + interval * num(k as f64)))
+        .collect();
+    let rest = duration - interval * num(samples as f64);
+    if rest > interval * num(1e-9) {
+        spans.push((rest, start + duration));
+    }
+    
+    let mut events = Vec::new();
+    let mut check = |controller: &Controller<B>| if let Some(detector) = detector.as_deref_mut() {
+        events.extend(detector.check(controller));
+    };
+    
+    sampler.sample(start, controller.iter())?;
+    check(controller);
+    for (span, time) in spans {
+        let steps = if adaptive { 1 } else { (span / delta).round().max(1.) as usize };
+        let step = span / num(steps as f64);
+        for _ in 0..steps {
+            controller.update(step);
+            check(controller);
+        }
+        sampler.sample(time, controller.iter())?;
+    }
+    Ok(events)
+}
+
+#[cfg(feature = "clap")]
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Config file to simulate
+    #[arg(default_value_t = crate::config::default_config_file())]
+    pub file_name: String,
+    /// Simulate a built-in scenario instead of the config file
+    #[arg(long, conflicts_with = "file_name")]
+    pub preset: Option<String>,
+    /// Simulated time to run for
+    #[arg(short, long)]
+    pub duration: Num,
+    /// Simulated time between two samples, every step by default
+    #[arg(short, long)]
+    pub interval: Option<Num>,
+    /// Output format, guessed from the output file name by default
+    #[arg(short, long, value_enum)]
+    pub format: Option<Format>,
+    /// Output file, stdout by default
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// CSV file for the events of the `[events]` table, listed on stderr by default
+    #[arg(short, long)]
+    pub events: Option<String>,
+}
+
+#[cfg(feature = "clap")]
+impl Args {
+    pub fn run<B>(&self) -> EResult<()>
+        where B: BodyLike + 'static, for<'de> B: Deserialize<'de>
+    {
+        let scenario: Scenario<B> = crate::config::load(&self.file_name, self.preset.as_deref())?;
+        scenario.check()?;
+        let delta = scenario.physics.delta;
+        let adaptive = scenario.physics.integrator.is_adaptive();
+        let interval = self.interval.unwrap_or(delta);
+        anyhow::ensure!(self.duration >= num(0), "Duration must not be negative");
+        anyhow::ensure!(interval > num(0), "Sample interval must be positive");
+        
+        let mut detector = Detector::new(scenario.physics.events);
+        let mut controller = scenario.controller();
+        let format = self.format
+            .unwrap_or_else(|| self.output.as_deref().map_or(Format::Csv, Format::of_file));
+        let out: Box<dyn Write> = match &self.output {
+            Some(name) => Box::new(
+                File::create(name).with_context(|| format!("Failed to create output file: {}", name))?
+            ),
+            None => Box::new(io::stdout().lock()),
+        };
+        let mut sampler = Sampler::new(BufWriter::new(out), format)?;
+        let detector = detector.settings().is_on().then_some(&mut detector);
+        let events = run(&mut controller, delta, adaptive, self.duration, interval, &mut sampler, detector)?;
+        sampler.finish()?;
+        
+        match &self.events {
+            Some(name) => events::write_csv(
+                BufWriter::new(File::create(name).with_context(|| format!("Failed to create events file: {}", name))?),
+                &events,
+            )?,
+            None => for event in &events {
+                eprintln!("{}", event);
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auto_impl_body;
+    use crate::body::BodyId;
+    use crate::vector::Vector;
+    use super::*;
+    
+    #[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+    struct Body {
+        id: BodyId,
+        pos: Vector,
+        speed: Vector,
+        mass: Num,
+    }
+    auto_impl_body!(pos, speed, mass, id, ());
+    
+    const BINARY: &str = r#"
+        integrator = "leapfrog"
+        delta = 0.001
+        gravitational_constant = 1
+        
+        [[bodies]]
+        id = 0
+        pos = [-0.5, 0, 0]
+        speed = [0, -0.5, 0]
+        mass = 0.5
+        
+        [[bodies]]
+        id = 1
+        pos = [0.5, 0, 0]
+        speed = [0, 0.5, 0]
+        mass = 0.5
+    "#;
+    
+    fn simulate(format: Format, duration: Num, interval: Num) -> String {
+        let scenario: Scenario<Body> = crate::config::parse_config(BINARY).unwrap();
+        let delta = scenario.physics.delta;
+        let mut con = scenario.controller();
+        let mut sampler = Sampler::new(Vec::new(), format).unwrap();
+        run(&mut con, delta, false, duration, interval, &mut sampler, None).unwrap();
+        String::from_utf8(sampler.finish().unwrap()).unwrap()
+    }
+    
+    #[test]
+    fn test_simulate_csv() {
+        let out = simulate(Format::Csv, num(1), num(0.25));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "time,id,x,y,z,vx,vy,vz");
+        assert_eq!(lines.len(), 1 + 5 * 2);
+        assert_eq!(lines[1], "0,0,-0.5,0,0,0,-0.5,0");
+        assert!(lines[10].starts_with("1,1,"));
+        
+        // A circular orbit of period 2 pi keeps its radius.
+        let fields: Vec<Num> = lines[10].split(',').map(|x| x.parse().unwrap()).collect();
+        let radius = Vector::new(fields[2], fields[3], fields[4]).module();
+        assert!((radius - num(0.5)).abs() < 1e-6, "radius {}", radius);
+    }
+    
+    #[test]
+    fn test_simulate_partial_interval() {
+        // Samples at 0, 0.3, 0.6 and 0.9, and at the end after a last interval of 0.1.
+        let out = simulate(Format::Csv, num(1), num(0.3));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 1 + 5 * 2);
+        assert!(lines[6].starts_with("0.6,1,"));
+        assert!(lines[10].starts_with("1,1,"));
+        
+        let fields: Vec<Num> = lines[10].split(',').map(|x| x.parse().unwrap()).collect();
+        let radius = Vector::new(fields[2], fields[3], fields[4]).module();
+        assert!((radius - num(0.5)).abs() < 1e-6, "radius {}", radius);
+        
+        // Nothing is left over past a whole number of intervals.
+        let out = simulate(Format::Csv, num(0.9), num(0.3));
+        assert_eq!(out.lines().count(), 1 + 4 * 2);
+    }
+    
+    #[test]
+    fn test_simulate_ndjson() {
+        let out = simulate(Format::Ndjson, num(0.5), num(0.1));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 6 * 2);
+        assert_eq!(lines[0], r#"{"time":0,"id":0,"pos":[-0.5,0,0],"speed":[0,-0.5,0]}"#);
+        assert!(lines[11].starts_with(r#"{"time":0.5,"id":1,"pos":["#));
+    }
+    
+    #[test]
+    fn test_simulate_scenario() {
+        let scenario: Scenario<Body> = crate::config::parse_config(BINARY).unwrap();
+        assert_eq!(scenario.physics.integrator, IntegratorKind::Leapfrog);
+        assert_eq!(scenario.physics.threads, Gravity::default().threads);
+        assert!(scenario.check().is_ok());
+        
+        for (mode, ok) in [("ignore", true), ("merge", false), ("pause", false)] {
+            let content = format!("collision = {:?}\n{}", mode, BINARY);
+            let scenario: Scenario<Body> = crate::config::parse_config(&content).unwrap();
+            assert_eq!(scenario.check().is_ok(), ok, "{}", mode);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use crate::num::{num, Num, PI};
+
+pub const GRAVITATIONAL_CONSTANT_SI: Num = 6.6743e-11;
+const DAY: Num = 86400.;
+const YEAR: Num = 365.25 * DAY;
+
+/// Exponents of length, mass and time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Dimension(i32, i32, i32);
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension(0, 0, 0);
+    pub const LENGTH: Dimension = Dimension(1, 0, 0);
+    pub const MASS: Dimension = Dimension(0, 1, 0);
+    pub const TIME: Dimension = Dimension(0, 0, 1);
+    pub const SPEED: Dimension = Dimension(1, 0, -1);
+    
+    fn times(self, other: Dimension, power: i32) -> Dimension {
+        Dimension(self.0 + other.0 * power, self.1 + other.1 * power, self.2 + other.2 * power)
+    }
+}
+
+static NAMED_UNITS: &[(&str, Num, Dimension)] = &[
+    ("m", 1., Dimension::LENGTH),
+    ("cm", 1e-2, Dimension::LENGTH),
+    ("km", 1e3, Dimension::LENGTH),
+    ("AU", 1.495978707e11, Dimension::LENGTH),
+    ("au", 1.495978707e11, Dimension::LENGTH),
+    ("ly", 9.4607304725808e15, Dimension::LENGTH),
+    ("pc", 3.085677581491367e16, Dimension::LENGTH),
+    ("Rsun", 6.957e8, Dimension::LENGTH),
+    ("Rearth", 6.371e6, Dimension::LENGTH),
+    ("kg", 1., Dimension::MASS),
+    ("g", 1e-3, Dimension::MASS),
+    ("Msun", 1.98847e30, Dimension::MASS),
+    ("Mjup", 1.89813e27, Dimension::MASS),
+    ("Mearth", 5.9722e24, Dimension::MASS),
+    ("Mmoon", 7.342e22, Dimension::MASS),
+    ("s", 1., Dimension::TIME),
+    ("min", 60., Dimension::TIME),
+    ("h", 3600., Dimension::TIME),
+    ("d", DAY, Dimension::TIME),
+    ("day", DAY, Dimension::TIME),
+    ("days", DAY, Dimension::TIME),
+    ("yr", YEAR, Dimension::TIME),
+    ("year", YEAR, Dimension::TIME),
+    ("years", YEAR, Dimension::TIME),
+    ("rad", 1., Dimension::NONE),
+    ("deg", PI / 180., Dimension::NONE),
+];
+
+/// A value in SI units, e.g. parsed from `"29.8 km/s"`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quantity {
+    pub si: Num,
+    pub dimension: Dimension,
+}
+
+fn parse_unit(expr: &str) -> Result<(Num, Dimension), String> {
+    let mut factor = num(1);
+    let mut dimension = Dimension::NONE;
+    for (i, part) in expr.split('/').enumerate() {
+        let sign = if i == 0 { 1 } else { -1 };
+        for term in part.split('*').map(str::trim) {
+            let (name, power) = match term.split_once('^') {
+                Some((name, power)) => (
+                    name,
+                    power.parse::<i32>().map_err(|_| format!("Invalid power in unit: {}", term))?,
+                ),
+                None => (term, 1),
+            };
+            let (_, scale, dim) = NAMED_UNITS.iter()
+                .find(|(x, ..)| *x == name)
+                .ok_or_else(|| format!("Unknown unit: {}", name))?;
+            factor *= scale.powi(sign * power);
+            dimension = dimension.times(*dim, sign * power);
+        }
+    }
+    Ok((factor, dimension))
+}
+
+impl std::str::FromStr for Quantity {
+    type Err = String;
+    
+    /// Accepts `"<number> <unit>"`, or a bare unit meaning one of it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (value, unit) = match s.split_once(char::is_whitespace) {
+            Some((value, unit)) => (value.parse::<Num>().map_err(|_| format!("Invalid number: {}", value))?, unit),
+            None => match s.parse::<Num>() {
+                Ok(value) => (value, ""),
+                Err(_) => (num(1), s),
+            },
+        };
+        let (factor, dimension) = if unit.trim().is_empty() {
+            (num(1), Dimension::NONE)
+        } else {
+            parse_unit(unit.trim())?
+        };
+        Ok(Self { si: value * factor, dimension })
+    }
+}
+
+/// The units a config is written in, from its `[units]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "UnitsHelper", into = "UnitsHelper")]
+pub struct Units {
+    names: UnitsHelper,
+    length: Num,
+    mass: Num,
+    time: Num,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct UnitsHelper {
+    length: String,
+    mass: String,
+    time: String,
+}
+
+impl TryFrom<UnitsHelper> for Units {
+    type Error = String;
+    
+    fn try_from(names: UnitsHelper) -> Result<Self, Self::Error> {
+        let base = |name: &str, expect: Dimension| {
+            let quantity: Quantity = name.parse()?;
+            if quantity.dimension == expect {
+                Ok(quantity.si)
+            } else {
+                Err(format!("Unit {} is not a {:?}", name, expect))
+            }
+        };
+        Ok(Self {
+            length: base(&names.length, Dimension::LENGTH)?,
+            mass: base(&names.mass, Dimension::MASS)?,
+            time: base(&names.time, Dimension::TIME)?,
+            names,
+        })
+    }
+}
+
+impl From<Units> for UnitsHelper {
+    fn from(units: Units) -> Self {
+        units.names
+    }
+}
+
+impl Units {
+    /// G expressed in these units.
+    pub fn gravitational_constant(&self) -> Num {
+        GRAVITATIONAL_CONSTANT_SI * self.mass * self.time * self.time / (self.length * self.length * self.length)
+    }
+    
+    pub fn convert(&self, quantity: Quantity) -> Num {
+        let Dimension(l, m, t) = quantity.dimension;
+        quantity.si / (self.length.powi(l) * self.mass.powi(m) * self.time.powi(t))
+    }
+    
+    pub fn seconds(&self, time: Num) -> Num {
+        time * self.time
+    }
+    
+    pub fn format_time(&self, time: Num) -> String {
+        let seconds = self.seconds(time);
+        if seconds.abs() >= YEAR {
+            format!("{:.3} years", seconds / YEAR)
+        } else {
+            format!("{:.3} days", seconds / DAY)
+        }
+    }
+}
+
+/// A config value, either a plain number in simulation units or a string with a unit suffix.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Plain(Num),
+    Tagged(String),
+}
+
+impl Value {
+    /// The value in `units`, which only values with a unit suffix need.
+    fn to_sim(&self, expect: Dimension, units: Option<&Units>) -> Result<Num, String> {
+        match self {
+            Value::Plain(x) => Ok(*x),
+            Value::Tagged(s) => {
+                let quantity: Quantity = s.parse()?;
+                if quantity.dimension == Dimension::NONE {
+                    return Ok(quantity.si);
+                }
+                if quantity.dimension != expect {
+                    return Err(format!("Expect a {:?} value, got: {}", expect, s));
+                }
+                let units = units.ok_or_else(|| format!("Unit in {} needs a [units] table", s))?;
+                Ok(units.convert(quantity))
+            }
+        }
+    }
+}
+
+/// Turn `value`, or each of its items if it is an array, into plain numbers in `units`.
+fn convert(value: &mut toml::Value, expect: Dimension, units: Option<&Units>) -> Result<(), String> {
+    if let toml::Value::Array(values) = value {
+        return values.iter_mut().try_for_each(|x| convert(x, expect, units));
+    }
+    let x = Value::deserialize(value.clone()).map_err(|_| format!("Expect a number, got: {}", value))?;
+    *value = toml::Value::Float(x.to_sim(expect, units)?);
+    Ok(())
+}
+
+/// Values of a body given in a unit, and of its orbit.
+static BODY_VALUES: &[(&str, Dimension)] = &[
+    ("pos", Dimension::LENGTH),
+    ("speed", Dimension::SPEED),
+    ("mass", Dimension::MASS),
+];
+static ORBIT_VALUES: &[(&str, Dimension)] = &[
+    ("a", Dimension::LENGTH),
+    ("i", Dimension::NONE),
+    ("omega", Dimension::NONE),
+    ("Omega", Dimension::NONE),
+    ("nu", Dimension::NONE),
+];
+
+/// Turn the values of the bodies of a config that have a unit suffix into plain numbers in `units`.
+pub fn convert_bodies(table: &mut toml::Table, units: Option<&Units>) -> Result<(), String> {
+    let Some(bodies) = table.get_mut("bodies").and_then(toml::Value::as_array_mut) else {
+        return Ok(());
+    };
+    for (index, body) in bodies.iter_mut().enumerate() {
+        let Some(body) = body.as_table_mut() else {
+            continue;
+        };
+        let name = body.get("id").map_or_else(|| format!("#{}", index), ToString::to_string);
+        let invalid = |key: &str, x: String| format!("Invalid {} of body {}: {}", key, name, x);
+        for (key, expect) in BODY_VALUES {
+            if let Some(value) = body.get_mut(*key) {
+                convert(value, *expect, units).map_err(|x| invalid(key, x))?;
+            }
+        }
+        if let Some(orbit) = body.get_mut("orbit").and_then(toml::Value::as_table_mut) {
+            for (key, expect) in ORBIT_VALUES {
+                if let Some(value) = orbit.get_mut(*key) {
+                    convert(value, *expect, units).map_err(|x| invalid(&format!("orbit.{}", key), x))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    fn close(x: Num, y: Num, precision: Num) -> bool {
+        ((x - y) / y).abs() < precision
+    }
+    
+    #[test]
+    fn test_units_parse() {
+        let speed: Quantity = "29.8 km/s".parse().unwrap();
+        assert_eq!(speed, Quantity { si: 29800., dimension: Dimension::SPEED });
+        let mass: Quantity = "Msun".parse().unwrap();
+        assert_eq!(mass.dimension, Dimension::MASS);
+        let acc: Quantity = "2 m/s^2".parse().unwrap();
+        assert_eq!(acc, Quantity { si: 2., dimension: Dimension(1, 0, -2) });
+        assert!("1 parsec".parse::<Quantity>().is_err());
+    }
+    
+    #[test]
+    fn test_units_convert() {
+        let units = Units::try_from(UnitsHelper {
+            length: "AU".into(),
+            mass: "Msun".into(),
+            time: "day".into(),
+        }).unwrap();
+        // Gaussian gravitational constant squared, up to the precision of G and Msun.
+        assert!(close(units.gravitational_constant(), 0.01720209895 * 0.01720209895, 1e-4));
+        let speed = units.convert("29.78 km/s".parse().unwrap());
+        assert!(close(speed, 29780. * DAY / 1.495978707e11, 1e-12));
+        assert_eq!(units.format_time(num(730.5)), "2.000 years");
+        assert_eq!(units.format_time(num(12)), "12.000 days");
+    }
+}